@@ -1,11 +1,50 @@
 extern crate jute_rust_codegen;
 
-use jute_rust_codegen::Runner;
+use jute_rust_codegen::{Nullability, Runner};
 
 fn main() {
-    Runner::new()
-        .add_file("src/clean_zookeeper.jute".to_string())
-        .set_output("src/generated.rs".to_string())
-        .run()
-        .expect("codegen to succeed");
+    let result = Runner::new()
+        // Includes `zookeeper.jute`.
+        .add_file("src/test.jute".to_string())
+        .set_derives(
+            [
+                "Debug",
+                "Clone",
+                "PartialEq",
+                "Eq",
+                "PartialOrd",
+                "Ord",
+                "Hash",
+                "Default",
+            ]
+            .iter()
+            .map(|derive| derive.to_string())
+            .collect(),
+        )
+        .set_builders(true)
+        .set_field_nullable(
+            "org.apache.zookeeper.proto.GetDataResponse.data".to_string(),
+            true,
+        )
+        .set_unsigned_byte("jute.rust.test.Bytes.raw".to_string())
+        .set_output("generated.rs".to_string())
+        .out_dir()
+        .run();
+
+    if let Err(error) = result {
+        panic!("codegen failed:\n{}", error);
+    }
+
+    let result = Runner::new()
+        .add_file("src/zookeeper.jute".to_string())
+        .set_nested_modules(true)
+        .rename_module("org.apache.zookeeper".to_string(), "zk".to_string())
+        .set_nullability(Nullability::Always)
+        .set_output("generated_nested.rs".to_string())
+        .out_dir()
+        .run();
+
+    if let Err(error) = result {
+        panic!("codegen failed:\n{}", error);
+    }
 }
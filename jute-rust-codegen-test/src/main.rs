@@ -1,6 +1,752 @@
-mod generated;
-use generated::org_apache_zookeeper_proto::{ConnectRequest, ConnectResponse};
+#[allow(dead_code, clippy::upper_case_acronyms)]
+mod generated {
+    jute_rust_runtime::include_generated!("generated.rs");
+}
+#[allow(dead_code, clippy::upper_case_acronyms)]
+mod generated_nested {
+    jute_rust_runtime::include_generated!("generated_nested.rs");
+}
+#[allow(dead_code, clippy::upper_case_acronyms)]
+mod included {
+    jute::include_schema!("src/zookeeper.jute", "src/test.jute");
+}
+#[allow(dead_code)]
+mod inline {
+    jute::schema! {
+        module example {
+            /// A point on the plane.
+            class Point {
+                int x;
+                int y; // Not part of the docs.
+                /// What the point is called.
+                ustring name;
+            }
+            class Path {
+                vector<example.Point> points;
+                map<ustring, vector<vector<long>>> weights;
+            }
+        }
+    }
+}
+use generated::org_apache_zookeeper_proto::ConnectRequest;
 
 fn main() {
-    println!("Hello, world!");
+    let request = ConnectRequest {
+        protocol_version: 0,
+        last_zxid_seen: 0,
+        time_out: 30000,
+        session_id: 0,
+        passwd: vec![0; 16],
+    };
+
+    let bytes = jute_rust_runtime::to_bytes(&request).expect("serialization to succeed");
+    println!("{:?} encodes to {:?}", request, bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use jute_rust_runtime::{
+        from_bytes, to_bytes, BuildError, JavaComparable, JavaHashCode, JavaSignature,
+        OutputArchive, Record,
+    };
+    use std::cmp::Ordering;
+    use std::collections::HashSet;
+    use std::io;
+
+    use crate::generated::jute_rust_test::{self, Bytes, Maps, Measurement, Nested, Series};
+    use crate::generated::org_apache_zookeeper_data::{Id, Stat, ACL};
+    use crate::generated::org_apache_zookeeper_proto::{
+        ConnectRequest, CreateRequest, GetDataRequest, SetDataResponse, SetWatches,
+    };
+
+    #[test]
+    fn test_connect_request() {
+        let request = ConnectRequest {
+            protocol_version: 0,
+            last_zxid_seen: 0x0102030405060708,
+            time_out: 30000,
+            session_id: -1,
+            passwd: vec![0xAB, 0xCD],
+        };
+
+        let bytes = to_bytes(&request).unwrap();
+        assert_eq!(
+            bytes,
+            vec![
+                0, 0, 0, 0, // protocolVersion
+                1, 2, 3, 4, 5, 6, 7, 8, // lastZxidSeen
+                0, 0, 0x75, 0x30, // timeOut
+                0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // sessionId
+                0, 0, 0, 2, 0xAB, 0xCD, // passwd
+            ]
+        );
+
+        let decoded = from_bytes::<ConnectRequest>(&bytes).unwrap();
+        assert_eq!(decoded, request);
+    }
+
+    #[test]
+    fn test_vector_of_records() {
+        let request = CreateRequest {
+            path: "/a".to_string(),
+            data: vec![],
+            acl: vec![ACL {
+                perms: 31,
+                id: Id {
+                    scheme: "world".to_string(),
+                    id: "anyone".to_string(),
+                },
+            }],
+            flags: 1,
+        };
+
+        let bytes = to_bytes(&request).unwrap();
+        assert_eq!(
+            bytes,
+            vec![
+                0, 0, 0, 2, b'/', b'a', // path
+                0, 0, 0, 0, // data
+                0, 0, 0, 1, // acl length
+                0, 0, 0, 31, // perms
+                0, 0, 0, 5, b'w', b'o', b'r', b'l', b'd', // scheme
+                0, 0, 0, 6, b'a', b'n', b'y', b'o', b'n', b'e', // id
+                0, 0, 0, 1, // flags
+            ]
+        );
+
+        let decoded = from_bytes::<CreateRequest>(&bytes).unwrap();
+        assert_eq!(decoded, request);
+    }
+
+    #[test]
+    fn test_vector_of_strings() {
+        let watches = SetWatches {
+            relative_zxid: 7,
+            data_watches: vec!["/x".to_string(), "".to_string()],
+            exist_watches: vec![],
+            child_watches: vec!["/".to_string()],
+        };
+
+        let bytes = to_bytes(&watches).unwrap();
+        assert_eq!(
+            bytes,
+            vec![
+                0, 0, 0, 0, 0, 0, 0, 7, // relativeZxid
+                0, 0, 0, 2, 0, 0, 0, 2, b'/', b'x', 0, 0, 0, 0, // dataWatches
+                0, 0, 0, 0, // existWatches
+                0, 0, 0, 1, 0, 0, 0, 1, b'/', // childWatches
+            ]
+        );
+
+        let decoded = from_bytes::<SetWatches>(&bytes).unwrap();
+        assert_eq!(decoded, watches);
+    }
+
+    #[test]
+    fn test_null_string_reads_as_empty() {
+        let bytes = [0xFF, 0xFF, 0xFF, 0xFF, 1];
+        let request = from_bytes::<GetDataRequest>(&bytes).unwrap();
+        assert_eq!(request.path, "");
+        assert!(request.watch);
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let stat = from_bytes::<Stat>(&[0u8; 10]);
+        assert!(stat.is_err());
+    }
+
+    /// Records the calls made by generated code instead of encoding them.
+    #[derive(Default)]
+    struct TraceArchive {
+        calls: Vec<String>,
+    }
+
+    impl TraceArchive {
+        fn trace(&mut self, call: String) -> io::Result<()> {
+            self.calls.push(call);
+            Ok(())
+        }
+    }
+
+    impl OutputArchive for TraceArchive {
+        fn write_byte(&mut self, value: i8, tag: &str) -> io::Result<()> {
+            self.trace(format!("byte {}={}", tag, value))
+        }
+
+        fn write_bool(&mut self, value: bool, tag: &str) -> io::Result<()> {
+            self.trace(format!("bool {}={}", tag, value))
+        }
+
+        fn write_int(&mut self, value: i32, tag: &str) -> io::Result<()> {
+            self.trace(format!("int {}={}", tag, value))
+        }
+
+        fn write_long(&mut self, value: i64, tag: &str) -> io::Result<()> {
+            self.trace(format!("long {}={}", tag, value))
+        }
+
+        fn write_float(&mut self, value: f32, tag: &str) -> io::Result<()> {
+            self.trace(format!("float {}={}", tag, value))
+        }
+
+        fn write_double(&mut self, value: f64, tag: &str) -> io::Result<()> {
+            self.trace(format!("double {}={}", tag, value))
+        }
+
+        fn write_string(&mut self, value: Option<&str>, tag: &str) -> io::Result<()> {
+            self.trace(format!("string {}={:?}", tag, value))
+        }
+
+        fn write_buffer(&mut self, value: Option<&[u8]>, tag: &str) -> io::Result<()> {
+            self.trace(format!("buffer {}={:?}", tag, value))
+        }
+
+        fn start_record(&mut self, tag: &str) -> io::Result<()> {
+            self.trace(format!("start_record {}", tag))
+        }
+
+        fn end_record(&mut self, tag: &str) -> io::Result<()> {
+            self.trace(format!("end_record {}", tag))
+        }
+
+        fn start_vector(&mut self, len: Option<usize>, tag: &str) -> io::Result<()> {
+            self.trace(format!("start_vector {}={:?}", tag, len))
+        }
+
+        fn end_vector(&mut self, tag: &str) -> io::Result<()> {
+            self.trace(format!("end_vector {}", tag))
+        }
+
+        fn start_map(&mut self, len: usize, tag: &str) -> io::Result<()> {
+            self.trace(format!("start_map {}={}", tag, len))
+        }
+
+        fn end_map(&mut self, tag: &str) -> io::Result<()> {
+            self.trace(format!("end_map {}", tag))
+        }
+    }
+
+    #[test]
+    fn test_archive_calls_and_tags() {
+        let acl = ACL {
+            perms: 1,
+            id: Id {
+                scheme: "digest".to_string(),
+                id: "a".to_string(),
+            },
+        };
+        let request = CreateRequest {
+            path: "/p".to_string(),
+            data: vec![7],
+            acl: vec![acl],
+            flags: 0,
+        };
+
+        let mut archive = TraceArchive::default();
+        request.serialize(&mut archive, "request").unwrap();
+        assert_eq!(
+            archive.calls,
+            vec![
+                "start_record request",
+                "string path=Some(\"/p\")",
+                "buffer data=Some([7])",
+                "start_vector acl=Some(1)",
+                "start_record e1",
+                "int perms=1",
+                "start_record id",
+                "string scheme=Some(\"digest\")",
+                "string id=Some(\"a\")",
+                "end_record id",
+                "end_record e1",
+                "end_vector acl",
+                "int flags=0",
+                "end_record request",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_maps() {
+        let mut maps = Maps {
+            counts: Default::default(),
+            ids: Default::default(),
+        };
+        maps.counts.insert("b".to_string(), 2);
+        maps.counts.insert("a".to_string(), 1);
+        maps.ids.insert(
+            7,
+            Id {
+                scheme: "ip".to_string(),
+                id: "".to_string(),
+            },
+        );
+
+        let bytes = to_bytes(&maps).unwrap();
+        assert_eq!(
+            bytes,
+            vec![
+                0, 0, 0, 2, // counts
+                0, 0, 0, 1, b'a', 0, 0, 0, 1, // a => 1
+                0, 0, 0, 1, b'b', 0, 0, 0, 2, // b => 2
+                0, 0, 0, 1, // ids
+                0, 0, 0, 0, 0, 0, 0, 7, // 7 =>
+                0, 0, 0, 2, b'i', b'p', 0, 0, 0, 0, // Id
+            ]
+        );
+
+        let decoded = from_bytes::<Maps>(&bytes).unwrap();
+        assert_eq!(decoded, maps);
+    }
+
+    #[test]
+    fn test_nested_containers() {
+        let mut nested = Nested {
+            matrix: vec![vec![1], vec![]],
+            acls: Default::default(),
+        };
+        nested.acls.insert(
+            "a".to_string(),
+            vec![ACL {
+                perms: 1,
+                id: Id {
+                    scheme: "s".to_string(),
+                    id: "i".to_string(),
+                },
+            }],
+        );
+
+        let bytes = to_bytes(&nested).unwrap();
+        assert_eq!(
+            bytes,
+            vec![
+                0, 0, 0, 2, // matrix
+                0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, // [1]
+                0, 0, 0, 0, // []
+                0, 0, 0, 1, // acls
+                0, 0, 0, 1, b'a', // "a" =>
+                0, 0, 0, 1, // one ACL
+                0, 0, 0, 1, 0, 0, 0, 1, b's', 0, 0, 0, 1, b'i', // ACL
+            ]
+        );
+
+        let decoded = from_bytes::<Nested>(&bytes).unwrap();
+        assert_eq!(decoded, nested);
+    }
+
+    #[test]
+    fn test_nested_modules() {
+        use crate::generated_nested::zk;
+
+        let response = zk::proto::SetDataResponse {
+            stat: zk::data::Stat {
+                czxid: 1,
+                mzxid: 2,
+                ctime: 3,
+                mtime: 4,
+                version: 5,
+                cversion: 6,
+                aversion: 7,
+                ephemeral_owner: 8,
+                data_length: 9,
+                num_children: 10,
+                pzxid: 11,
+            },
+        };
+
+        let bytes = to_bytes(&response).unwrap();
+        let flat = from_bytes::<SetDataResponse>(&bytes).unwrap();
+        assert_eq!(flat.stat.pzxid, 11);
+        assert_eq!(to_bytes(&flat).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_colliding_names() {
+        let option = jute_rust_test::Option {
+            value: jute_rust_test::String {
+                self_: "s".to_string(),
+                archive: vec!["a".to_string()],
+                r#type: 2,
+            },
+            e1: 3,
+        };
+
+        let bytes = to_bytes(&option).unwrap();
+        assert_eq!(
+            bytes,
+            vec![
+                0, 0, 0, 1, b's', // self
+                0, 0, 0, 1, 0, 0, 0, 1, b'a', // archive
+                0, 0, 0, 0, 0, 0, 0, 2, // type
+                0, 0, 0, 3, // e1
+            ]
+        );
+
+        let decoded = from_bytes::<jute_rust_test::Option>(&bytes).unwrap();
+        assert_eq!(decoded, option);
+    }
+
+    #[test]
+    fn test_derives() {
+        let id = Id {
+            scheme: "world".to_string(),
+            id: "anyone".to_string(),
+        };
+        let ids: HashSet<Id> = vec![id.clone(), id.clone()].into_iter().collect();
+        assert_eq!(ids.len(), 1);
+        assert_eq!(Stat::default().czxid, 0);
+
+        let series = Series {
+            name: "latency".to_string(),
+            points: vec![Measurement {
+                value: 1.5,
+                error: 0.25,
+            }],
+        };
+        let decoded = from_bytes::<Series>(&to_bytes(&series).unwrap()).unwrap();
+        assert_eq!(decoded, series);
+        assert_ne!(decoded, Series::default());
+    }
+
+    #[test]
+    fn test_java_hash_codes() {
+        // The values Java's generated `hashCode` methods return.
+        let id = Id {
+            scheme: "world".to_string(),
+            id: "anyone".to_string(),
+        };
+        assert_eq!(id.java_hash_code(), -1514785795);
+
+        let request = GetDataRequest {
+            path: "/a".to_string(),
+            watch: true,
+        };
+        assert_eq!(request.java_hash_code(), 80771);
+
+        let request = ConnectRequest {
+            protocol_version: 0,
+            last_zxid_seen: 0x0102030405060708,
+            time_out: 30000,
+            session_id: -1,
+            passwd: vec![0xAB, 0xCD],
+        };
+        assert_eq!(request.java_hash_code(), -1220056056);
+    }
+
+    #[test]
+    fn test_java_ordering() {
+        use crate::generated::org_apache_zookeeper_proto::GetSASLRequest;
+
+        let id = |scheme: &str, id: &str| Id {
+            scheme: scheme.to_string(),
+            id: id.to_string(),
+        };
+        assert!(id("digest", "b") < id("world", "a"));
+        assert!(id("world", "a") < id("world", "b"));
+        // Java compares strings by UTF-16 code units.
+        assert_eq!(
+            id("\u{FFFD}", "").cmp(&id("\u{10000}", "")),
+            Ordering::Greater
+        );
+
+        let request = |watch: bool| GetDataRequest {
+            path: "/".to_string(),
+            watch,
+        };
+        assert!(request(false) < request(true));
+
+        // Java compares buffers as signed bytes, then by length.
+        let sasl = |token: &[u8]| GetSASLRequest {
+            token: token.to_vec(),
+        };
+        assert!(sasl(&[0x80]) < sasl(&[0x7F]));
+        assert!(sasl(&[0x7F]) < sasl(&[0x7F, 0x00]));
+        assert_eq!(sasl(&[0xFF]).compare_to(&sasl(&[0x00])), Ordering::Less);
+
+        let mut ids = [id("world", "anyone"), id("auth", ""), id("digest", "x")];
+        ids.sort();
+        assert_eq!(ids[0], id("auth", ""));
+
+        // Java orders NaN above everything, which only `compare_to` keeps.
+        let measurement = |value: f32| Measurement { value, error: 0.0 };
+        assert_eq!(measurement(f32::NAN).partial_cmp(&measurement(1.0)), None);
+        assert_eq!(measurement(1.0).partial_cmp(&measurement(f32::NAN)), None);
+        assert_eq!(
+            measurement(f32::NAN).compare_to(&measurement(1.0)),
+            Ordering::Greater
+        );
+        assert!(measurement(1.0) < measurement(2.0));
+
+        let empty = jute_rust_test::Empty {};
+        assert_eq!(empty.compare_to(&jute_rust_test::Empty {}), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_signatures() {
+        use crate::generated::org_apache_zookeeper_txn::TxnHeader;
+
+        // The values Java's generated `signature()` methods return.
+        assert_eq!(TxnHeader::SIGNATURE, "LTxnHeader(lilli)");
+        assert_eq!(Stat::SIGNATURE, "LStat(lllliiiliil)");
+        assert_eq!(
+            CreateRequest::SIGNATURE,
+            "LCreateRequest(sB[LACL(iLId(ss))]i)"
+        );
+        assert_eq!(Maps::SIGNATURE, "LMaps({si}{lLId(ss)})");
+    }
+
+    #[test]
+    fn test_builders() {
+        let request = ConnectRequest::builder()
+            .protocol_version(0)
+            .time_out(30000)
+            .build()
+            .unwrap();
+        assert_eq!(request.time_out, 30000);
+        assert_eq!(request.session_id, 0);
+        assert!(request.passwd.is_empty());
+
+        let acl = ACL::builder()
+            .perms(31)
+            .id(Id::builder().scheme("world".to_string()).build().unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(acl.id.scheme, "world");
+        assert_eq!(acl.id.id, "");
+
+        assert_eq!(
+            SetDataResponse::builder().build(),
+            Err(BuildError::MissingField {
+                record: "SetDataResponse",
+                field: "stat"
+            })
+        );
+    }
+
+    #[test]
+    fn test_nullable_fields() {
+        use crate::generated::org_apache_zookeeper_proto::GetDataResponse;
+        use crate::generated_nested::zk;
+
+        for (data, length) in [(None, [0xFF; 4]), (Some(vec![]), [0; 4])] {
+            let response = GetDataResponse {
+                data,
+                stat: Stat::default(),
+            };
+            let bytes = to_bytes(&response).unwrap();
+            assert_eq!(bytes[..4], length);
+            assert_eq!(from_bytes::<GetDataResponse>(&bytes).unwrap(), response);
+        }
+
+        for (children, length) in [(None, [0xFF; 4]), (Some(vec![]), [0; 4])] {
+            let response = zk::proto::GetChildrenResponse { children };
+            let bytes = to_bytes(&response).unwrap();
+            assert_eq!(bytes, length);
+            let decoded = from_bytes::<zk::proto::GetChildrenResponse>(&bytes).unwrap();
+            assert_eq!(decoded.children, response.children);
+        }
+
+        let response = zk::proto::GetChildrenResponse {
+            children: Some(vec!["a".to_string()]),
+        };
+        let bytes = to_bytes(&response).unwrap();
+        assert_eq!(bytes, vec![0, 0, 0, 1, 0, 0, 0, 1, b'a']);
+
+        let request = zk::proto::GetDataRequest {
+            path: None,
+            watch: false,
+        };
+        let bytes = to_bytes(&request).unwrap();
+        assert_eq!(bytes, vec![0xFF, 0xFF, 0xFF, 0xFF, 0]);
+        let decoded = from_bytes::<zk::proto::GetDataRequest>(&bytes).unwrap();
+        assert_eq!(decoded.path, None);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let bytes = Bytes {
+            signed: -1,
+            raw: 0xFF,
+        };
+        let encoded = to_bytes(&bytes).unwrap();
+        assert_eq!(encoded, vec![0xFF, 0xFF]);
+        assert_eq!(from_bytes::<Bytes>(&encoded).unwrap(), bytes);
+
+        // Java orders both fields as signed bytes.
+        let small = Bytes { signed: 0, raw: 0 };
+        assert!(bytes < small);
+        assert!(
+            Bytes {
+                signed: 0,
+                raw: 0x80
+            } < small
+        );
+        assert_eq!(Bytes::SIGNATURE, "LBytes(bb)");
+        assert_eq!(bytes.java_hash_code(), (17 * 37 - 1) * 37 - 1);
+    }
+
+    #[test]
+    fn test_included_schema() {
+        use crate::included::org_apache_zookeeper_data as included;
+
+        let acl = ACL {
+            perms: 31,
+            id: Id {
+                scheme: "world".to_string(),
+                id: "anyone".to_string(),
+            },
+        };
+        let decoded = from_bytes::<included::ACL>(&to_bytes(&acl).unwrap()).unwrap();
+        assert_eq!(decoded.perms, 31);
+        assert_eq!(decoded.id.scheme, "world");
+        assert_eq!(included::ACL::SIGNATURE, ACL::SIGNATURE);
+        assert_eq!(
+            crate::included::jute_rust_test::Maps::SIGNATURE,
+            Maps::SIGNATURE
+        );
+    }
+
+    #[test]
+    fn test_inline_schema() {
+        use crate::inline::example::{Path, Point};
+
+        let point = Point {
+            x: 1,
+            y: -1,
+            name: "origin".to_string(),
+        };
+        let bytes = to_bytes(&point).unwrap();
+        assert_eq!(&bytes[..8], &[0, 0, 0, 1, 0xFF, 0xFF, 0xFF, 0xFF]);
+        let decoded = from_bytes::<Point>(&bytes).unwrap();
+        assert_eq!(decoded.name, "origin");
+        assert_eq!(Point::SIGNATURE, "LPoint(iis)");
+        assert_eq!(Path::SIGNATURE, "LPath([LPoint(iis)]{s[[l]]})");
+    }
+
+    #[test]
+    fn test_derived_records() {
+        use crate::generated::org_apache_zookeeper_proto::GetDataResponse;
+        use crate::generated::org_apache_zookeeper_txn::TxnHeader;
+        use jute::JuteRecord;
+        use std::collections::HashMap;
+
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Zxid(i64);
+
+        impl From<Zxid> for i64 {
+            fn from(zxid: Zxid) -> i64 {
+                zxid.0
+            }
+        }
+
+        impl From<i64> for Zxid {
+            fn from(zxid: i64) -> Zxid {
+                Zxid(zxid)
+            }
+        }
+
+        #[derive(Debug, PartialEq, JuteRecord)]
+        struct Header {
+            client_id: i64,
+            cxid: i32,
+            #[jute(type = "long")]
+            zxid: Zxid,
+            time: i64,
+            r#type: i32,
+        }
+
+        let header = Header {
+            client_id: 1,
+            cxid: 2,
+            zxid: Zxid(0x1_0000_0003),
+            time: 4,
+            r#type: -5,
+        };
+        let bytes = to_bytes(&header).unwrap();
+        let generated = from_bytes::<TxnHeader>(&bytes).unwrap();
+        assert_eq!(generated.zxid, 0x1_0000_0003);
+        assert_eq!(generated.r#type, -5);
+        assert_eq!(to_bytes(&generated).unwrap(), bytes);
+        assert_eq!(from_bytes::<Header>(&bytes).unwrap(), header);
+        // The Java traits agree with the generated code too.
+        assert_eq!(header.java_hash_code(), generated.java_hash_code());
+        let later = Header {
+            zxid: Zxid(0x1_0000_0004),
+            ..header
+        };
+        assert_eq!(header.compare_to(&later), Ordering::Less);
+        assert_eq!(Header::SIGNATURE, "LHeader(lilli)");
+
+        #[derive(Debug, PartialEq, JuteRecord)]
+        struct DataResponse {
+            #[jute(nullable)]
+            data: Option<Vec<u8>>,
+            stat: Stat,
+        }
+
+        for data in [None, Some(vec![]), Some(vec![0xFF])] {
+            let response = DataResponse {
+                data,
+                stat: Stat::default(),
+            };
+            let bytes = to_bytes(&response).unwrap();
+            let generated = from_bytes::<GetDataResponse>(&bytes).unwrap();
+            assert_eq!(generated.data, response.data);
+            assert_eq!(to_bytes(&generated).unwrap(), bytes);
+            assert_eq!(from_bytes::<DataResponse>(&bytes).unwrap(), response);
+        }
+        // Nested records compare and write their signatures themselves.
+        let response = |version: i32| DataResponse {
+            data: None,
+            stat: Stat {
+                version,
+                ..Stat::default()
+            },
+        };
+        assert_eq!(response(1).compare_to(&response(2)), Ordering::Less);
+        assert_eq!(
+            DataResponse::signature(),
+            GetDataResponse::SIGNATURE.replace("LGetDataResponse", "LDataResponse")
+        );
+
+        #[derive(Debug, PartialEq, JuteRecord)]
+        struct Containers {
+            matrix: Vec<Vec<i64>>,
+            acls: HashMap<String, Vec<ACL>>,
+        }
+
+        let mut acls = HashMap::new();
+        acls.insert(
+            "/".to_string(),
+            vec![ACL {
+                perms: 31,
+                id: Id {
+                    scheme: "world".to_string(),
+                    id: "anyone".to_string(),
+                },
+            }],
+        );
+        let containers = Containers {
+            matrix: vec![vec![1, 2], vec![]],
+            acls,
+        };
+        let bytes = to_bytes(&containers).unwrap();
+        let generated = from_bytes::<Nested>(&bytes).unwrap();
+        assert_eq!(generated.matrix, containers.matrix);
+        assert_eq!(to_bytes(&generated).unwrap(), bytes);
+        assert_eq!(from_bytes::<Containers>(&bytes).unwrap(), containers);
+        assert_eq!(containers.java_hash_code(), generated.java_hash_code());
+        assert_eq!(
+            Containers::signature(),
+            Nested::SIGNATURE.replace("LNested", "LContainers")
+        );
+
+        // Like in Java, the signature of a record nested in itself stops at
+        // its name.
+        #[derive(JuteRecord)]
+        struct Tree {
+            children: Vec<Tree>,
+        }
+        assert_eq!(Tree::signature(), "LTree([LTree])");
+    }
 }
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+
+use jute_rust_parser::{ClassId, FieldType, PrimitiveFieldType, Schema};
+
+use crate::{MapType, Options};
+
+/// Returns the traits to derive on the struct of every class: the configured
+/// derives, less the standard traits some field type does not implement.
+///
+/// Classes Java can compare get the `PartialOrd` and `Ord` of their list
+/// implemented with Java's ordering instead of derived; both still appear in
+/// the list.
+pub(crate) fn class_derives(schema: &Schema, options: &Options) -> HashMap<ClassId, Vec<String>> {
+    let mut derives = HashMap::new();
+    for (module_index, module) in schema.modules.iter().enumerate() {
+        for (class_index, class) in module.classes.iter().enumerate() {
+            let name = format!("{}.{}", module.name, class.name);
+            let requested = options
+                .class_derives
+                .iter()
+                .rev()
+                .find(|(class, _)| *class == name)
+                .map_or(&options.derives, |(_, derives)| derives);
+            let id = ClassId {
+                module: module_index,
+                class: class_index,
+            };
+            derives.insert(id, requested.clone());
+        }
+    }
+
+    // Classes may refer to each other in cycles, so drop unsupported traits
+    // until no class changes any more.
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let ids: Vec<ClassId> = derives.keys().copied().collect();
+        for id in ids {
+            let class = schema.class(id);
+            let supported: Vec<String> = derives[&id]
+                .iter()
+                .filter(|derive| {
+                    let name = trait_name(derive);
+                    let requirements_met = requirements(name).iter().all(|required| {
+                        derives[&id]
+                            .iter()
+                            .any(|derive| trait_name(derive) == *required)
+                    });
+                    requirements_met
+                        && class
+                            .fields
+                            .iter()
+                            .all(|field| implements(&field.field_type, name, &derives, options))
+                })
+                .cloned()
+                .collect();
+            if supported.len() != derives[&id].len() {
+                derives.insert(id, supported);
+                changed = true;
+            }
+        }
+    }
+    derives
+}
+
+/// Returns the classes Java generates a working `compareTo` for: those
+/// without vector or map fields, that only contain such classes.
+pub(crate) fn comparable_classes(schema: &Schema) -> HashSet<ClassId> {
+    let mut comparable = HashSet::new();
+    for (module_index, module) in schema.modules.iter().enumerate() {
+        for class_index in 0..module.classes.len() {
+            comparable.insert(ClassId {
+                module: module_index,
+                class: class_index,
+            });
+        }
+    }
+
+    let mut changed = true;
+    while changed {
+        let before = comparable.len();
+        let ids: Vec<ClassId> = comparable.iter().copied().collect();
+        for id in ids {
+            let fields = &schema.class(id).fields;
+            let is_comparable = fields.iter().all(|field| match &field.field_type {
+                FieldType::Primitive(PrimitiveFieldType::Custom(reference)) => {
+                    let target = reference.target.expect("schema to be resolved");
+                    comparable.contains(&target)
+                }
+                FieldType::Primitive(_) => true,
+                FieldType::Vector(_) | FieldType::Map(_, _) => false,
+            });
+            if !is_comparable {
+                comparable.remove(&id);
+            }
+        }
+        changed = comparable.len() != before;
+    }
+    comparable
+}
+
+/// Strips the path from a derive such as `std::hash::Hash`.
+pub(crate) fn trait_name(derive: &str) -> &str {
+    derive.rsplit("::").next().unwrap_or(derive)
+}
+
+/// Returns whether `derives` include serde's, which name fields after the
+/// Rust field rather than the Jute one unless told otherwise.
+pub(crate) fn derives_serde(derives: &[String]) -> bool {
+    derives.iter().any(|derive| {
+        derive.starts_with("serde::") || matches!(trait_name(derive), "Serialize" | "Deserialize")
+    })
+}
+
+/// The traits a standard trait cannot be derived without.
+fn requirements(name: &str) -> &'static [&'static str] {
+    match name {
+        "Copy" => &["Clone"],
+        "Eq" => &["PartialEq"],
+        "PartialOrd" => &["PartialEq"],
+        "Ord" => &["PartialOrd", "Eq"],
+        _ => &[],
+    }
+}
+
+/// Returns whether the Rust type generated for `t` implements the trait
+/// `name`. Traits other than the standard derivable ones are assumed to be
+/// implemented.
+fn implements(
+    t: &FieldType,
+    name: &str,
+    derives: &HashMap<ClassId, Vec<String>>,
+    options: &Options,
+) -> bool {
+    match t {
+        FieldType::Primitive(PrimitiveFieldType::Float)
+        | FieldType::Primitive(PrimitiveFieldType::Double) => {
+            !matches!(name, "Eq" | "Hash" | "Ord")
+        }
+        FieldType::Primitive(PrimitiveFieldType::String)
+        | FieldType::Primitive(PrimitiveFieldType::Buffer) => name != "Copy",
+        FieldType::Primitive(PrimitiveFieldType::Custom(reference)) => {
+            let target = reference.target.expect("schema to be resolved");
+            derives[&target]
+                .iter()
+                .any(|derive| trait_name(derive) == name)
+                || !is_standard(name)
+        }
+        FieldType::Primitive(_) => true,
+        FieldType::Vector(element_type) => {
+            name != "Copy" && implements(element_type, name, derives, options)
+        }
+        FieldType::Map(key_type, value_type) => {
+            let unordered = options.map_type == MapType::HashMap
+                && matches!(name, "Hash" | "PartialOrd" | "Ord");
+            name != "Copy"
+                && !unordered
+                && implements(key_type, name, derives, options)
+                && implements(value_type, name, derives, options)
+        }
+    }
+}
+
+/// Returns the traits the configured map type needs of its keys that the
+/// key type of a map in `t` does not implement.
+pub(crate) fn missing_key_traits(
+    t: &FieldType,
+    derives: &HashMap<ClassId, Vec<String>>,
+    options: &Options,
+) -> Vec<&'static str> {
+    match t {
+        FieldType::Primitive(_) => vec![],
+        FieldType::Vector(element_type) => missing_key_traits(element_type, derives, options),
+        FieldType::Map(key_type, value_type) => {
+            let required: &[&'static str] = match options.map_type {
+                MapType::BTreeMap => &["Ord"],
+                MapType::HashMap => &["Eq", "Hash"],
+            };
+            let mut missing: Vec<&'static str> = required
+                .iter()
+                .copied()
+                .filter(|name| !implements(key_type, name, derives, options))
+                .collect();
+            for nested in [key_type, value_type] {
+                for name in missing_key_traits(nested, derives, options) {
+                    if !missing.contains(&name) {
+                        missing.push(name);
+                    }
+                }
+            }
+            missing
+        }
+    }
+}
+
+/// Whether `name` is a standard trait whose derive is checked.
+fn is_standard(name: &str) -> bool {
+    matches!(
+        name,
+        "Clone" | "Copy" | "Debug" | "Default" | "Eq" | "Hash" | "Ord" | "PartialEq" | "PartialOrd"
+    )
+}
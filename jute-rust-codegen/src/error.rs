@@ -0,0 +1,106 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+use jute_rust_parser::{ParseError, ResolveError, Span};
+
+use crate::MapType;
+
+/// A single problem found while generating code, attributed to a file.
+#[derive(Debug)]
+pub enum Diagnostic {
+    /// A schema could not be read, or the output could not be written.
+    Io { file: String, error: io::Error },
+    /// A schema is not valid Jute.
+    Parse { file: String, error: ParseError },
+    /// A schema refers to a class that does not exist, or defines one twice.
+    Resolve { file: String, error: ResolveError },
+    /// The keys of a map field lack traits its Rust collection needs, such
+    /// as `Ord` for `BTreeMap`.
+    MapKey {
+        file: String,
+        class: String,
+        field: String,
+        map_type: MapType,
+        missing: Vec<&'static str>,
+        span: Span,
+    },
+}
+
+impl Diagnostic {
+    pub fn file(&self) -> &str {
+        match self {
+            Diagnostic::Io { file, .. }
+            | Diagnostic::Parse { file, .. }
+            | Diagnostic::Resolve { file, .. }
+            | Diagnostic::MapKey { file, .. } => file,
+        }
+    }
+
+    /// The region of the file the problem is in, if it is in the schema.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Diagnostic::Io { .. } => None,
+            Diagnostic::Parse { error, .. } => Some(error.span()),
+            Diagnostic::Resolve { error, .. } => Some(error.span()),
+            Diagnostic::MapKey { span, .. } => Some(*span),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::Io { file, error } => write!(f, "{}: {}", file, error),
+            Diagnostic::Parse { file, error } => write!(f, "{}:{}", file, error),
+            Diagnostic::Resolve { file, error } => write!(f, "{}:{}", file, error),
+            Diagnostic::MapKey {
+                file,
+                class,
+                field,
+                map_type,
+                missing,
+                span,
+            } => {
+                let missing: Vec<String> =
+                    missing.iter().map(|name| format!("`{}`", name)).collect();
+                write!(
+                    f,
+                    "{}:{}: the map keys of field `{}` in class `{}` do not implement {}, which `{:?}` needs",
+                    file,
+                    span,
+                    field,
+                    class,
+                    missing.join(" and "),
+                    map_type
+                )
+            }
+        }
+    }
+}
+
+/// Every problem `Runner::run` or `Runner::generate` found, in the order of
+/// the inputs.
+#[derive(Debug)]
+pub struct Error {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Error {
+    pub(crate) fn new(diagnostics: Vec<Diagnostic>) -> Error {
+        Error { diagnostics }
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let messages: Vec<String> = self.diagnostics.iter().map(ToString::to_string).collect();
+        f.write_str(&messages.join("\n"))
+    }
+}
+
+impl error::Error for Error {}
@@ -1,27 +1,135 @@
 extern crate codegen;
 extern crate jute_rust_parser;
 
+mod derives;
+mod error;
+mod record;
+mod signature;
+
+pub use error::*;
+pub use record::*;
+
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
 use std::fs;
 use std::io;
+use std::path::{Path, PathBuf};
 
-use codegen::Scope;
-use jute_rust_parser::{FieldType, Module, Parser, PrimitiveFieldType};
+use codegen::{Block, Function, Module, Scope};
+use jute_rust_parser::{
+    Class, ClassId, Comments, Field, FieldType, Parser, PrimitiveFieldType, Schema, TypeReference,
+};
 
 pub struct Runner {
-    files: Vec<String>,
+    inputs: Vec<Input>,
     output_file: String,
+    /// Whether `output_file` is a name inside `$OUT_DIR`.
+    out_dir: bool,
+    options: Options,
+}
+
+/// A schema to generate code for.
+enum Input {
+    File(String),
+    /// A schema given as text, with the name it is reported under.
+    Source {
+        name: String,
+        text: String,
+    },
+}
+
+/// Settings that shape the generated code.
+#[derive(Clone)]
+struct Options {
+    map_type: MapType,
+    root_module: Option<String>,
+    nested_modules: bool,
+    /// Jute module name prefixes and their replacements.
+    module_renames: Vec<(String, String)>,
+    derives: Vec<String>,
+    /// Fully qualified Jute class names and the derives replacing `derives`.
+    class_derives: Vec<(String, Vec<String>)>,
+    builders: bool,
+    nullability: Nullability,
+    /// Fully qualified Jute field names and whether they are nullable.
+    nullable_fields: Vec<(String, bool)>,
+    /// Fully qualified Jute names of the fields whose bytes are `u8`.
+    unsigned_bytes: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            map_type: MapType::BTreeMap,
+            root_module: None,
+            nested_modules: false,
+            module_renames: vec![],
+            derives: vec!["Debug".to_string()],
+            class_derives: vec![],
+            builders: false,
+            nullability: Nullability::Never,
+            nullable_fields: vec![],
+            unsigned_bytes: vec![],
+        }
+    }
+}
+
+/// The Rust collection generated for Jute `map<K, V>` fields.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MapType {
+    /// `std::collections::BTreeMap`, iterating in key order like the
+    /// `TreeMap` Java's generated code deserializes into.
+    BTreeMap,
+    /// `std::collections::HashMap`.
+    HashMap,
+}
+
+impl MapType {
+    fn path(self) -> &'static str {
+        match self {
+            MapType::BTreeMap => "::std::collections::BTreeMap",
+            MapType::HashMap => "::std::collections::HashMap",
+        }
+    }
+}
+
+/// Whether `ustring`, `buffer` and `vector` fields, which Java writes with a
+/// length of `-1` when they are null, are generated as `Option`s.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Nullability {
+    /// Null values read back as empty ones.
+    Never,
+    /// `None` stands for null, so null and empty values round-trip.
+    Always,
+}
+
+impl Default for Runner {
+    fn default() -> Runner {
+        Runner::new()
+    }
 }
 
 impl Runner {
     pub fn new() -> Runner {
         Runner {
-            files: vec![],
+            inputs: vec![],
             output_file: String::new(),
+            out_dir: false,
+            options: Options::default(),
         }
     }
 
     pub fn add_file(&mut self, file: String) -> &mut Runner {
-        self.files.push(file);
+        self.inputs.push(Input::File(file));
+        self
+    }
+
+    /// Adds a schema given as text rather than as a file. `name` takes the
+    /// place of the file name in diagnostics, and the files it includes are
+    /// looked up relative to the current directory.
+    pub fn add_source(&mut self, name: String, text: String) -> &mut Runner {
+        self.inputs.push(Input::Source { name, text });
         self
     }
 
@@ -30,63 +138,1369 @@ impl Runner {
         self
     }
 
-    pub fn run(&self) -> io::Result<()> {
+    /// Writes the output into `$OUT_DIR`, where build scripts are supposed
+    /// to write, as `jute.rs` or under the name given to `set_output`. `run`
+    /// then also tells cargo to rerun the build script when any schema file
+    /// changes, including the ones they include. The `include_generated!`
+    /// macro of `jute-rust-runtime` includes the output.
+    pub fn out_dir(&mut self) -> &mut Runner {
+        self.out_dir = true;
+        self
+    }
+
+    /// Chooses the collection generated for map fields, `BTreeMap` unless set.
+    /// Map keys have to implement `Ord` for `BTreeMap`, or `Eq` and `Hash` for
+    /// `HashMap`, so `float` and `double` keys are errors, and so are records
+    /// whose derives lack these traits.
+    pub fn set_map_type(&mut self, map_type: MapType) -> &mut Runner {
+        self.options.map_type = map_type;
+        self
+    }
+
+    /// Makes references between generated modules absolute paths starting
+    /// at `root`, the path of the module the generated code is included in,
+    /// such as `crate::generated`. By default they are relative `super::`
+    /// paths, which work wherever the code is included.
+    pub fn set_root_module(&mut self, root: String) -> &mut Runner {
+        self.options.root_module = Some(root);
+        self
+    }
+
+    /// Generates a Rust module for every part of a Jute module name, so that
+    /// `org.apache.zookeeper.proto` becomes `org::apache::zookeeper::proto`
+    /// rather than `org_apache_zookeeper_proto`.
+    pub fn set_nested_modules(&mut self, nested: bool) -> &mut Runner {
+        self.options.nested_modules = nested;
+        self
+    }
+
+    /// Replaces the prefix `from` of Jute module names with `to` before they
+    /// become Rust modules. Renaming `org.apache.zookeeper` to `zk` turns
+    /// `org.apache.zookeeper.proto` into `zk.proto`; an empty `to` strips the
+    /// prefix. The longest matching prefix wins.
+    pub fn rename_module(&mut self, from: String, to: String) -> &mut Runner {
+        self.options.module_renames.push((from, to));
+        self
+    }
+
+    /// Sets the traits derived on every generated struct, `Debug` unless set.
+    /// Standard traits that a struct's fields do not implement are left out,
+    /// such as `Eq` and `Hash` for classes that contain a `float` or `double`.
+    /// On classes Java can compare, `PartialOrd` and `Ord` follow Java's
+    /// `compareTo`, except that `partial_cmp` leaves NaN unordered. With
+    /// serde's derives, fields whose Rust name differs from the Jute name are
+    /// renamed back to it with `#[serde(rename)]`.
+    pub fn set_derives(&mut self, derives: Vec<String>) -> &mut Runner {
+        self.options.derives = derives;
+        self
+    }
+
+    /// Derives `derives` instead of the global list on the struct generated
+    /// for `class`, a fully qualified Jute name such as
+    /// `org.apache.zookeeper.data.Id`.
+    pub fn set_class_derives(&mut self, class: String, derives: Vec<String>) -> &mut Runner {
+        self.options.class_derives.push((class, derives));
+        self
+    }
+
+    /// Generates a builder for every class, so that
+    /// `ConnectRequest::builder().time_out(30000).build()` replaces a struct
+    /// literal. Fields that are not set take their default value, except for
+    /// nested records, which make `build` fail. The setters of fields named
+    /// `build` or `default` are prefixed with `set_`, and a builder whose name
+    /// is taken by a class gets a number, like `FooBuilder1`, as their docs
+    /// note.
+    pub fn set_builders(&mut self, builders: bool) -> &mut Runner {
+        self.options.builders = builders;
+        self
+    }
+
+    /// Chooses whether `ustring`, `buffer` and `vector` fields can be null,
+    /// `Nullability::Never` unless set.
+    pub fn set_nullability(&mut self, nullability: Nullability) -> &mut Runner {
+        self.options.nullability = nullability;
+        self
+    }
+
+    /// Overrides the nullability of a single field, given by its fully
+    /// qualified Jute name such as
+    /// `org.apache.zookeeper.proto.GetDataResponse.data`. Fields of other
+    /// types than `ustring`, `buffer` and `vector` are never nullable.
+    pub fn set_field_nullable(&mut self, field: String, nullable: bool) -> &mut Runner {
+        self.options.nullable_fields.push((field, nullable));
+        self
+    }
+
+    /// Generates the `byte`s of a field, given by its fully qualified Jute
+    /// name, as `u8` rather than `i8`. They are still written and ordered as
+    /// Java's signed bytes.
+    pub fn set_unsigned_byte(&mut self, field: String) -> &mut Runner {
+        self.options.unsigned_bytes.push(field);
+        self
+    }
+
+    /// Generates code for all files and writes it to the output. Nothing is
+    /// written if any file fails to parse or resolve; every problem is
+    /// reported in the returned error and, inside a build script, as a cargo
+    /// warning.
+    pub fn run(&self) -> Result<(), Error> {
+        let (result, files) = self.generate_tracked();
+        if self.out_dir {
+            for file in &files {
+                println!("cargo:rerun-if-changed={}", file);
+            }
+        }
+
+        let result = result.and_then(|code| {
+            let output = self.output_path()?;
+            fs::write(&output, code).map_err(|error| {
+                Error::new(vec![Diagnostic::Io {
+                    file: output.to_string_lossy().into_owned(),
+                    error,
+                }])
+            })
+        });
+
+        // Build scripts run with `OUT_DIR` set; cargo shows their warnings
+        // even when the build succeeds or fails for another reason.
+        if let Err(error) = &result {
+            if env::var_os("OUT_DIR").is_some() {
+                for diagnostic in error.diagnostics() {
+                    println!("cargo:warning={}", diagnostic);
+                }
+            }
+        }
+        result
+    }
+
+    /// Generates code for all inputs and returns it rather than writing it
+    /// to the output. Every problem is reported in the returned error.
+    pub fn generate(&self) -> Result<String, Error> {
+        self.generate_tracked().0
+    }
+
+    /// Like `generate`, but also returns every schema file read, including
+    /// the ones other files include. Files are listed even if they could not
+    /// be read or parsed, so that a build can be rerun once they are fixed.
+    pub fn generate_tracked(&self) -> (Result<String, Error>, Vec<String>) {
         let mut generator = Generator {
             scope: Scope::new(),
+            options: self.options.clone(),
         };
+        let mut inputs = Inputs::default();
+        for input in &self.inputs {
+            match input {
+                Input::File(file) => inputs.read_file(file),
+                Input::Source { name, text } => inputs.parse(name, text, None),
+            }
+        }
+
+        let Inputs {
+            mut schema,
+            module_files,
+            files,
+            mut diagnostics,
+            ..
+        } = inputs;
+        if diagnostics.is_empty() {
+            if let Err(errors) = schema.resolve() {
+                diagnostics.extend(errors.into_iter().map(|error| Diagnostic::Resolve {
+                    file: module_files[error.module()].clone(),
+                    error,
+                }));
+            }
+        }
+
+        if diagnostics.is_empty() {
+            diagnostics.extend(map_key_diagnostics(&schema, &self.options, &module_files));
+        }
 
-        for file in &self.files {
-            let contents = fs::read_to_string(file)?;
-            let mut parser = Parser::from_string(&contents);
+        if !diagnostics.is_empty() {
+            return (Err(Error::new(diagnostics)), files);
+        }
+        generator.generate(&schema);
+        (Ok(generator.to_string()), files)
+    }
+
+    /// Returns the file `run` writes to.
+    fn output_path(&self) -> Result<PathBuf, Error> {
+        if !self.out_dir {
+            return Ok(PathBuf::from(&self.output_file));
+        }
+
+        let name = if self.output_file.is_empty() {
+            "jute.rs"
+        } else {
+            &self.output_file
+        };
+        match env::var_os("OUT_DIR") {
+            Some(dir) => Ok(Path::new(&dir).join(name)),
+            None => Err(Error::new(vec![Diagnostic::Io {
+                file: name.to_string(),
+                error: io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "`OUT_DIR` is not set, so this is not a build script",
+                ),
+            }])),
+        }
+    }
+}
+
+/// The schemas read so far, together with everything they include.
+#[derive(Default)]
+struct Inputs {
+    schema: Schema,
+    /// The file each module of `schema` comes from.
+    module_files: Vec<String>,
+    /// Every file read or attempted to, in order.
+    files: Vec<String>,
+    /// The files in `files`, by their canonical paths.
+    seen: HashSet<PathBuf>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Inputs {
+    /// Reads and parses `file`, unless it has been already.
+    fn read_file(&mut self, file: &str) {
+        let path = fs::canonicalize(file).unwrap_or_else(|_| PathBuf::from(file));
+        if !self.seen.insert(path) {
+            return;
+        }
+
+        self.files.push(file.to_string());
+        match fs::read_to_string(file) {
+            Ok(contents) => self.parse(file, &contents, Path::new(file).parent()),
+            Err(error) => self.diagnostics.push(Diagnostic::Io {
+                file: file.to_string(),
+                error,
+            }),
+        }
+    }
+
+    /// Parses the schema `contents` of `file`, then reads the files it
+    /// includes, looking in `dir` first like Java's Jute compiler does.
+    fn parse(&mut self, file: &str, contents: &str, dir: Option<&Path>) {
+        match Parser::from_string(contents).parse_all() {
+            Ok(schema) => {
+                self.module_files.resize(
+                    self.module_files.len() + schema.modules.len(),
+                    file.to_string(),
+                );
+                self.schema.modules.extend(schema.modules);
+                for include in schema.includes {
+                    let path = match dir.map(|dir| dir.join(&include.path)) {
+                        Some(path) if path.exists() => path.to_string_lossy().into_owned(),
+                        _ => include.path,
+                    };
+                    self.read_file(&path);
+                }
+            }
+            Err(errors) => {
+                self.diagnostics
+                    .extend(errors.into_iter().map(|error| Diagnostic::Parse {
+                        file: file.to_string(),
+                        error,
+                    }))
+            }
+        }
+    }
+}
+
+pub struct Generator {
+    scope: Scope,
+    options: Options,
+}
+
+/// Reports the map fields of a resolved schema whose keys cannot be keys of
+/// the configured map type, with the derives the classes end up with.
+fn map_key_diagnostics(
+    schema: &Schema,
+    options: &Options,
+    module_files: &[String],
+) -> Vec<Diagnostic> {
+    let derives = derives::class_derives(schema, options);
+    let mut diagnostics = vec![];
+    for (module_index, module) in schema.modules.iter().enumerate() {
+        for class in &module.classes {
+            for field in &class.fields {
+                let missing = derives::missing_key_traits(&field.field_type, &derives, options);
+                if !missing.is_empty() {
+                    diagnostics.push(Diagnostic::MapKey {
+                        file: module_files[module_index].clone(),
+                        class: format!("{}.{}", module.name, class.name),
+                        field: field.name.clone(),
+                        map_type: options.map_type,
+                        missing,
+                        span: field.span,
+                    });
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+impl Generator {
+    /// Generates code for all modules of a resolved schema.
+    fn generate(&mut self, schema: &Schema) {
+        let comparable = derives::comparable_classes(schema);
+        let derives = derives::class_derives(schema, &self.options);
+
+        // `codegen` cannot document modules, so the notes on renamed modules
+        // and `byte` fields are inner doc comments, which have to precede
+        // every item of their module. The modules that need them are therefore
+        // created first, parents before their children, and each gets every
+        // note once.
+        let mut module_notes: Vec<(Vec<String>, Vec<String>)> = vec![];
+        let mut add_note = |path: &[String], note: String| match module_notes
+            .iter_mut()
+            .find(|(notes_path, _)| notes_path == path)
+        {
+            Some((_, notes)) if notes.contains(&note) => {}
+            Some((_, notes)) => notes.push(note),
+            None => module_notes.push((path.to_vec(), vec![note])),
+        };
+        for module in &schema.modules {
+            let path = self.options.module_path(&module.name);
+            let unsanitized = self.options.unsanitized_module_path(&module.name);
+            for (depth, (segment, name)) in path.iter().zip(&unsanitized).enumerate() {
+                if unraw(segment) != name {
+                    let mut note = String::new();
+                    add_rename_note(&mut note, name);
+                    add_note(&path[..=depth], format!("//! {}", note));
+                }
+            }
+            let has_bytes = module.classes.iter().any(|class| {
+                class
+                    .fields
+                    .iter()
+                    .any(|field| contains_byte(&field.field_type))
+            });
+            if has_bytes {
+                add_note(&path, BYTE_NOTE.to_string());
+            }
+        }
+        module_notes.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        for (path, notes) in &module_notes {
+            module_at(&mut self.scope, path)
+                .scope()
+                .raw(&notes.join("\n//!\n"));
+        }
 
-            loop {
-                match parser.next() {
-                    Ok(module) => {
-                        let code = generator.generate(&module);
+        for (module_index, module) in schema.modules.iter().enumerate() {
+            let types = Types {
+                schema,
+                jute_module: &module.name,
+                module: self.options.module_path(&module.name),
+                options: &self.options,
+                unsigned_bytes: false,
+            };
+
+            let rust_module = module_at(&mut self.scope, &types.module);
+
+            let builder_names = builder_names(&module.classes);
+            for (class_index, class) in module.classes.iter().enumerate() {
+                let struct_name = rust_class_name(&class.name);
+                let created_struct = rust_module.new_struct(&struct_name).vis("pub");
+                let id = ClassId {
+                    module: module_index,
+                    class: class_index,
+                };
+                let is_comparable = comparable.contains(&id);
+                let mut ordering = vec![];
+                for derive in &derives[&id] {
+                    match derives::trait_name(derive) {
+                        name @ ("PartialOrd" | "Ord") if is_comparable => ordering.push(name),
+                        _ => {
+                            created_struct.derive(derive);
+                        }
+                    }
+                }
+
+                let mut doc = doc_text(&class.comments);
+                if unraw(&struct_name) != class.name {
+                    add_rename_note(&mut doc, &class.name);
+                }
+                if !doc.is_empty() {
+                    created_struct.doc(&doc);
+                }
+
+                let serde = derives::derives_serde(&derives[&id]);
+                for (field, field_name) in class.fields.iter().zip(rust_field_names(class)) {
+                    let mut doc = doc_text(&field.comments);
+                    if unraw(&field_name) != snake_case(&field.name) {
+                        add_rename_note(&mut doc, &field.name);
                     }
-                    Err(e) => {
-                        eprintln!("{}", e);
-                        break;
+
+                    // `codegen` has no field documentation or attributes, so
+                    // these are emitted as part of the field name.
+                    let mut declaration = String::new();
+                    for line in doc.lines() {
+                        declaration.push_str(format!("/// {}", line).trim_end());
+                        declaration.push('\n');
+                    }
+                    if serde && unraw(&field_name) != field.name {
+                        declaration.push_str(&format!("#[serde(rename = {:?})]\n", field.name));
                     }
+                    declaration.push_str(&format!("pub {}", field_name));
+                    created_struct.field(&declaration, types.field_type(class, field));
+                }
+
+                // `codegen` cannot declare associated constants.
+                rust_module.scope().raw(&format!(
+                    "impl {} {{\n    \
+                     /// The signature Java's Jute compiler gives this record, which\n    \
+                     /// its peers compare to detect schema mismatches.\n    \
+                     pub const SIGNATURE: &'static str = {:?};\n}}",
+                    struct_name,
+                    signature::class_signature(schema, class)
+                ));
+
+                let fields = record_fields(class, &types);
+                let class_impl = rust_module
+                    .new_impl(&struct_name)
+                    .impl_trait(format!("{}::Record", RUNTIME));
+                class_impl.push_fn(generate_serialize(&fields));
+                class_impl.push_fn(generate_deserialize(&fields));
+
+                rust_module
+                    .new_impl(&struct_name)
+                    .impl_trait(format!("{}::JavaHashCode", RUNTIME))
+                    .push_fn(generate_java_hash_code(&fields));
+                rust_module
+                    .new_impl(&struct_name)
+                    .impl_trait(format!("{}::JavaSignature", RUNTIME))
+                    .push_fn(generate_write_signature());
+                if is_comparable {
+                    rust_module
+                        .new_impl(&struct_name)
+                        .impl_trait(format!("{}::JavaComparable", RUNTIME))
+                        .push_fn(generate_compare_to(&fields));
+                }
+                if ordering.contains(&"PartialOrd") {
+                    rust_module
+                        .new_impl(&struct_name)
+                        .impl_trait("::std::cmp::PartialOrd")
+                        .push_fn(generate_partial_cmp(&fields, ordering.contains(&"Ord")));
+                }
+                if ordering.contains(&"Ord") {
+                    let mut cmp = Function::new("cmp");
+                    cmp.arg_ref_self()
+                        .arg("other", "&Self")
+                        .ret(ORDERING)
+                        .line(format!(
+                            "{}::JavaComparable::compare_to(self, other)",
+                            RUNTIME
+                        ));
+                    rust_module
+                        .new_impl(&struct_name)
+                        .impl_trait("::std::cmp::Ord")
+                        .push_fn(cmp);
+                }
+                if self.options.builders {
+                    let builder_name = &builder_names[class_index];
+                    generate_builder(rust_module, class, &struct_name, builder_name, &types);
                 }
             }
         }
+    }
+}
+
+/// Returns the public module at `path` in `scope`, creating it and its
+/// parents where they do not exist yet.
+fn module_at<'a>(scope: &'a mut Scope, path: &[String]) -> &'a mut Module {
+    let mut module = scope.get_or_new_module(&path[0]).vis("pub");
+    for segment in &path[1..] {
+        module = module.get_or_new_module(segment).vis("pub");
+    }
+    module
+}
 
-        let code = generator.to_string();
-        let result = fs::write(&self.output_file, code);
-        if result.is_err() {
-            eprintln!("return");
-            return result;
+impl fmt::Display for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.scope.to_string())
+    }
+}
+
+/// Joins the comments of a declaration into the text of its rustdoc.
+fn doc_text(comments: &Comments) -> String {
+    let mut lines = comments.leading.clone();
+    lines.extend(comments.trailing.clone());
+    lines.join("\n")
+}
+
+/// Appends the note recording that a declaration was renamed to a doc text.
+fn add_rename_note(doc: &mut String, jute_name: &str) {
+    if !doc.is_empty() {
+        doc.push_str("\n\n");
+    }
+    doc.push_str(&format!("Named `{}` in the Jute schema.", jute_name));
+}
+
+/// Returns the Rust name of a field: the Jute name in snake_case, made a
+/// valid identifier. Archives still see the Jute name as tag.
+fn rust_field_name(name: &str) -> String {
+    sanitize(&snake_case(name))
+}
+
+/// Returns the Rust names of the fields of a class. A field whose name is
+/// already taken by an earlier one, such as `foo_bar` after `fooBar`, gets
+/// the first free numbered suffix.
+fn rust_field_names(class: &Class) -> Vec<String> {
+    let names: Vec<String> = class
+        .fields
+        .iter()
+        .map(|field| rust_field_name(&field.name))
+        .collect();
+    let mut unique: Vec<String> = vec![];
+    for name in &names {
+        let mut candidate = name.clone();
+        let mut suffix = 1;
+        while unique.contains(&candidate) || (candidate != *name && names.contains(&candidate)) {
+            candidate = format!("{}_{}", unraw(name), suffix);
+            suffix += 1;
         }
+        unique.push(candidate);
+    }
+    unique
+}
 
-        Ok(())
+/// Returns the Rust name of a class, renamed if it would shadow a primitive
+/// type.
+fn rust_class_name(name: &str) -> String {
+    if PRIMITIVE_TYPES.contains(&name) {
+        format!("{}_", name)
+    } else {
+        sanitize(name)
     }
 }
 
-pub struct Generator {
-    scope: Scope,
+/// Turns a name into a Rust identifier: keywords become raw identifiers,
+/// and the keywords that cannot be raw get a trailing underscore instead.
+fn sanitize(name: &str) -> String {
+    if PATH_KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else if KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    } else {
+        name.to_string()
+    }
 }
 
-impl Generator {
-    fn generate(&mut self, module: &Module) {
-        let rust_module = self
-            .scope
-            .get_or_new_module(&jute_module_to_rust(module.name.clone()));
+/// Strips the `r#` of a raw identifier, which does not rename anything.
+fn unraw(identifier: &str) -> &str {
+    identifier.strip_prefix("r#").unwrap_or(identifier)
+}
 
-        for class in &module.classes {
-            let created_struct = rust_module.new_struct(&class.name).derive("Debug");
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake_case = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next_is_lowercase = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+            // Split `lastZxid` and `URLFormat`, but not `ACL`.
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_is_lowercase)
+            {
+                snake_case.push('_');
+            }
+        }
+        snake_case.extend(c.to_lowercase());
+    }
+    snake_case
+}
 
-            for field in &class.fields {
-                created_struct.field(
-                    &format!("r#{}", field.name),
-                    &jute_to_rust_type(field.field_type.clone()),
-                );
+/// Returns the bindings `deserialize` reads the fields `field_names` into,
+/// with underscores appended where they would shadow a name the generated
+/// code itself uses, or the binding of another field.
+fn local_names(field_names: &[&str]) -> Vec<String> {
+    let is_reserved = |name: &str| {
+        let is_loop_variable = ["len", "e", "k", "v"].iter().any(|prefix| {
+            name.strip_prefix(prefix)
+                .is_some_and(|depth| !depth.is_empty() && depth.chars().all(|c| c.is_ascii_digit()))
+        });
+        is_loop_variable || name == "archive" || name == "tag"
+    };
+    let mut locals: Vec<String> = vec![];
+    for &name in field_names {
+        let mut candidate = name.to_string();
+        while is_reserved(&candidate)
+            || locals.contains(&candidate)
+            || (candidate != name && field_names.contains(&candidate.as_str()))
+        {
+            candidate = format!("{}_", unraw(&candidate));
+        }
+        locals.push(candidate);
+    }
+    locals
+}
+
+/// Keywords that start paths and cannot be raw identifiers.
+const PATH_KEYWORDS: &[&str] = &["self", "Self", "super", "crate"];
+
+/// Strict and reserved Rust keywords that can be used as raw identifiers.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+/// Primitive types, which a struct of the same name would shadow.
+const PRIMITIVE_TYPES: &[&str] = &[
+    "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "str", "u8", "u16",
+    "u32", "u64", "u128", "usize",
+];
+
+/// Paths of the standard library items generated code uses, which a Jute
+/// class of the same name would otherwise shadow.
+const STRING: &str = "::std::string::String";
+const VEC: &str = "::std::vec::Vec";
+const SOME: &str = "::std::option::Option::Some";
+const OK: &str = "::std::result::Result::Ok";
+const ORDERING: &str = "::std::cmp::Ordering";
+
+/// Documentation of the modules that contain `byte` fields.
+const BYTE_NOTE: &str =
+    "//! Jute's `byte` is Java's signed `byte`, so `byte` fields are `i8` unless
+//! configured to be `u8`. Either way, they are written and ordered like
+//! Java's signed bytes, and a `u8` field above 127 reads back unchanged but
+//! compares as negative.";
+
+/// Path under which generated code reaches `jute-rust-runtime`.
+const RUNTIME: &str = "::jute_rust_runtime";
+
+/// A field of a record as the emitters see it.
+struct RecordField<'a> {
+    /// The Jute field, whose name archives see as tag.
+    field: &'a Field,
+    /// The name of the struct field.
+    name: String,
+    nullable: bool,
+    /// Whether the struct field has a type of its own, which converts with
+    /// `From` to and from the Rust type of the Jute type.
+    converted: bool,
+    /// The types as seen from the field.
+    types: Types<'a>,
+}
+
+impl RecordField<'_> {
+    /// Returns the expression for the value of the field in `record`, such
+    /// as `self`: the struct field, or the binding `bind_converted` made.
+    fn place(&self, record: &str) -> String {
+        if self.converted {
+            format!("{}_{}", record, unraw(&self.name))
+        } else {
+            format!("{}.{}", record, self.name)
+        }
+    }
+
+    /// Returns the Rust type of the Jute type of the field.
+    fn rust_type(&self) -> String {
+        let rust_type = self.types.rust_type(&self.field.field_type);
+        if self.nullable {
+            format!("::std::option::Option<{}>", rust_type)
+        } else {
+            rust_type
+        }
+    }
+}
+
+/// Returns the fields of a class as the emitters see them.
+fn record_fields<'a>(class: &'a Class, types: &Types<'a>) -> Vec<RecordField<'a>> {
+    class
+        .fields
+        .iter()
+        .zip(rust_field_names(class))
+        .map(|(field, name)| RecordField {
+            field,
+            name,
+            nullable: types.is_nullable(class, field),
+            converted: false,
+            types: types.for_field(class, field),
+        })
+        .collect()
+}
+
+/// Emits the bindings `RecordField::place` names for the converted fields of
+/// `record`.
+fn bind_converted(function: &mut Function, fields: &[RecordField], record: &str) {
+    for field in fields.iter().filter(|field| field.converted) {
+        let value = format!("::std::clone::Clone::clone(&{}.{})", record, field.name);
+        function.line(format!(
+            "let {}: {} = {};",
+            field.place(record),
+            field.rust_type(),
+            convert(&value, field.nullable)
+        ));
+    }
+}
+
+/// Returns the expression converting `value` with `From`, inside its
+/// `Option` if `nullable`.
+fn convert(value: &str, nullable: bool) -> String {
+    if nullable {
+        format!("{}.map(::std::convert::From::from)", value)
+    } else {
+        format!("::std::convert::From::from({})", value)
+    }
+}
+
+/// Emits `Record::serialize`, mirroring the `serialize` method Java's Jute
+/// compiler generates.
+fn generate_serialize(fields: &[RecordField]) -> Function {
+    let mut function = Function::new("serialize");
+    function
+        .generic(&format!("A: {}::OutputArchive", RUNTIME))
+        .arg_ref_self()
+        .arg("archive", "&mut A")
+        .arg("tag", "&str")
+        .ret("::std::io::Result<()>");
+
+    bind_converted(&mut function, fields, "self");
+    function.line("archive.start_record(tag)?;");
+    for field in fields {
+        let place = field.place("self");
+        let value = if field.nullable {
+            Value::Nullable(place)
+        } else {
+            Value::Place(place)
+        };
+        write_value(
+            &mut function,
+            &field.field.field_type,
+            &value,
+            &field.field.name,
+            1,
+            &field.types,
+        );
+    }
+    function.line("archive.end_record(tag)");
+    function
+}
+
+/// Emits `Record::deserialize`, mirroring the `deserialize` method Java's
+/// Jute compiler generates.
+fn generate_deserialize(fields: &[RecordField]) -> Function {
+    let mut function = Function::new("deserialize");
+    function
+        .generic(&format!("A: {}::InputArchive", RUNTIME))
+        .arg("archive", "&mut A")
+        .arg("tag", "&str")
+        .ret("::std::io::Result<Self>");
+
+    let names: Vec<&str> = fields.iter().map(|field| field.name.as_str()).collect();
+    let locals = local_names(&names);
+    function.line("archive.start_record(tag)?;");
+    for (field, local) in fields.iter().zip(&locals) {
+        read_value(
+            &mut function,
+            &field.field.field_type,
+            local,
+            &field.field.name,
+            1,
+            field.nullable,
+            &field.types,
+        );
+        if field.converted {
+            function.line(format!(
+                "let {} = {};",
+                local,
+                convert(local, field.nullable)
+            ));
+        }
+    }
+    function.line("archive.end_record(tag)?;");
+
+    let mut result = Block::new(&format!("{}(Self", OK));
+    for (field, local) in fields.iter().zip(&locals) {
+        if *local == field.name {
+            result.line(format!("{},", field.name));
+        } else {
+            result.line(format!("{}: {},", field.name, local));
+        }
+    }
+    result.after(")");
+    function.push_block(result);
+    function
+}
+
+/// Returns the names of the builders of the classes of a module, numbered
+/// where the name is taken by a class or an earlier builder.
+fn builder_names(classes: &[Class]) -> Vec<String> {
+    let class_names: Vec<String> = classes
+        .iter()
+        .map(|class| unraw(&rust_class_name(&class.name)).to_string())
+        .collect();
+    let mut builder_names: Vec<String> = vec![];
+    for class_name in &class_names {
+        let mut candidate = format!("{}Builder", class_name);
+        let mut suffix = 1;
+        while class_names.contains(&candidate) || builder_names.contains(&candidate) {
+            candidate = format!("{}Builder{}", class_name, suffix);
+            suffix += 1;
+        }
+        builder_names.push(candidate);
+    }
+    builder_names
+}
+
+/// Returns the names of the setters of the fields `field_names`, prefixed
+/// with `set_` where they would clash with the builder's `build` method or
+/// `Default::default`.
+fn setter_names(field_names: &[String]) -> Vec<String> {
+    let mut setter_names: Vec<String> = vec![];
+    for name in field_names {
+        let mut candidate = name.clone();
+        while matches!(candidate.as_str(), "build" | "default")
+            || setter_names.contains(&candidate)
+            || (candidate != *name && field_names.contains(&candidate))
+        {
+            candidate = format!("set_{}", unraw(&candidate));
+        }
+        setter_names.push(candidate);
+    }
+    setter_names
+}
+
+/// Emits a builder named `builder_name` for `class` next to its struct
+/// `struct_name`, and the `builder` function creating it.
+fn generate_builder(
+    module: &mut Module,
+    class: &Class,
+    struct_name: &str,
+    builder_name: &str,
+    types: &Types,
+) {
+    let mut builder = Function::new("builder");
+    builder
+        .vis("pub")
+        .ret(builder_name)
+        .line(if class.fields.is_empty() {
+            builder_name.to_string()
+        } else {
+            format!("{}::default()", builder_name)
+        });
+    module.new_impl(struct_name).push_fn(builder);
+
+    let mut doc = format!("Builds a [`{}`].", struct_name);
+    let default_name = format!("{}Builder", unraw(struct_name));
+    if builder_name != default_name {
+        doc.push_str(&format!(
+            "\n\nNamed `{}` because `{}` is taken.",
+            builder_name, default_name
+        ));
+    }
+    let builder_struct = module
+        .new_struct(builder_name)
+        .vis("pub")
+        .derive("Default")
+        .doc(&doc);
+    let field_names = rust_field_names(class);
+    for (field, name) in class.fields.iter().zip(&field_names) {
+        builder_struct.field(
+            name,
+            format!("::std::option::Option<{}>", types.field_type(class, field)),
+        );
+    }
+
+    let builder_impl = module.new_impl(builder_name);
+    let setter_names = setter_names(&field_names);
+    for ((field, name), setter_name) in class.fields.iter().zip(&field_names).zip(&setter_names) {
+        let mut setter = Function::new(setter_name);
+        setter
+            .vis("pub")
+            .arg_self()
+            .arg("value", types.field_type(class, field))
+            .ret("Self")
+            .line(if class.fields.len() == 1 {
+                format!("Self {{ {}: {}(value) }}", name, SOME)
+            } else {
+                format!("Self {{ {}: {}(value), ..self }}", name, SOME)
+            });
+        let mut doc = doc_text(&field.comments);
+        if setter_name != name {
+            if !doc.is_empty() {
+                doc.push_str("\n\n");
+            }
+            doc.push_str(&format!(
+                "Sets `{}`, whose name is taken by another method.",
+                unraw(name)
+            ));
+        }
+        if !doc.is_empty() {
+            setter.doc(&doc);
+        }
+        builder_impl.push_fn(setter);
+    }
+
+    let mut build = Function::new("build");
+    build.vis("pub").arg_self().ret(format!(
+        "::std::result::Result<{}, {}::BuildError>",
+        struct_name, RUNTIME
+    ));
+    let mut result = Block::new(&format!("{}({}", OK, struct_name));
+    for (field, name) in class.fields.iter().zip(&field_names) {
+        let value = match &field.field_type {
+            FieldType::Primitive(PrimitiveFieldType::Custom(_)) => format!(
+                "self.{}.ok_or({}::BuildError::MissingField {{ record: {:?}, field: {:?} }})?",
+                name,
+                RUNTIME,
+                struct_name,
+                unraw(name)
+            ),
+            _ => format!("self.{}.unwrap_or_default()", name),
+        };
+        result.line(format!("{}: {},", name, value));
+    }
+    result.after(")");
+    build.push_block(result);
+    builder_impl.push_fn(build);
+}
+
+/// Emits `JavaSignature::write_signature` for a record with a `SIGNATURE`
+/// constant.
+fn generate_write_signature() -> Function {
+    let mut function = Function::new("write_signature");
+    function
+        .arg("signature", "&mut ::std::string::String")
+        .arg("_enclosing", "&mut ::std::vec::Vec<&'static str>")
+        .line("signature.push_str(Self::SIGNATURE);");
+    function
+}
+
+/// Emits `JavaHashCode::java_hash_code`, mirroring the `hashCode` method
+/// Java's Jute compiler generates.
+fn generate_java_hash_code(fields: &[RecordField]) -> Function {
+    let mut function = Function::new("java_hash_code");
+    function.arg_ref_self().ret("i32");
+
+    if fields.is_empty() {
+        function.line("17");
+        return function;
+    }
+    bind_converted(&mut function, fields, "self");
+    function.line("let mut result: i32 = 17;");
+    for field in fields {
+        let place = field.place("self");
+        let hash = match &field.field.field_type {
+            FieldType::Primitive(PrimitiveFieldType::Boolean) => {
+                format!("if {} {{ 0 }} else {{ 1 }}", place)
+            }
+            FieldType::Primitive(PrimitiveFieldType::Buffer) if field.nullable => {
+                format!("{}::buffer_hash_code({}.as_deref())", RUNTIME, place)
+            }
+            FieldType::Primitive(PrimitiveFieldType::Buffer) => {
+                format!("{}::buffer_hash_code({}(&{}))", RUNTIME, SOME, place)
+            }
+            _ => format!("{}::JavaHashCode::java_hash_code(&{})", RUNTIME, place),
+        };
+        function.line(format!(
+            "result = result.wrapping_mul(37).wrapping_add({});",
+            hash
+        ));
+    }
+    function.line("result");
+    function
+}
+
+/// Emits `JavaComparable::compare_to`, mirroring the `compareTo` method Java's
+/// Jute compiler generates: fields compare in declaration order.
+fn generate_compare_to(fields: &[RecordField]) -> Function {
+    let mut function = Function::new("compare_to");
+    if fields.is_empty() {
+        function.arg_ref_self().arg("_other", "&Self").ret(ORDERING);
+        function.line(format!("{}::Equal", ORDERING));
+        return function;
+    }
+    function.arg_ref_self().arg("other", "&Self").ret(ORDERING);
+
+    bind_converted(&mut function, fields, "self");
+    bind_converted(&mut function, fields, "other");
+    for (i, field) in fields.iter().enumerate() {
+        let compare = format!(
+            "{}::JavaComparable::compare_to(&{}, &{})",
+            RUNTIME,
+            field.place("self"),
+            field.place("other")
+        );
+        if i == 0 {
+            function.line(compare);
+        } else {
+            function.line(format!("    .then_with(|| {})", compare));
+        }
+    }
+    function
+}
+
+/// Emits `PartialOrd::partial_cmp` for a class Java can compare. With `Ord`
+/// it agrees with `cmp`; without, floating point fields compare as in Rust,
+/// so that NaN is unordered rather than greater than everything like in Java.
+fn generate_partial_cmp(fields: &[RecordField], has_ord: bool) -> Function {
+    let mut function = Function::new("partial_cmp");
+    let other = if fields.is_empty() && !has_ord {
+        "_other"
+    } else {
+        "other"
+    };
+    function
+        .arg_ref_self()
+        .arg(other, "&Self")
+        .ret(format!("::std::option::Option<{}>", ORDERING));
+
+    if has_ord {
+        function.line(format!("{}(self.cmp(other))", SOME));
+        return function;
+    }
+    if fields.is_empty() {
+        function.line(format!("{}({}::Equal)", SOME, ORDERING));
+        return function;
+    }
+    bind_converted(&mut function, fields, "self");
+    bind_converted(&mut function, fields, "other");
+    for (i, field) in fields.iter().enumerate() {
+        let (place, other) = (field.place("self"), field.place("other"));
+        let compare = match &field.field.field_type {
+            FieldType::Primitive(PrimitiveFieldType::Float)
+            | FieldType::Primitive(PrimitiveFieldType::Double)
+            | FieldType::Primitive(PrimitiveFieldType::Custom(_)) => format!(
+                "::std::cmp::PartialOrd::partial_cmp(&{}, &{})",
+                place, other
+            ),
+            _ => format!(
+                "{}({}::JavaComparable::compare_to(&{}, &{}))",
+                SOME, RUNTIME, place, other
+            ),
+        };
+        if i + 1 == fields.len() {
+            function.line(compare);
+        } else {
+            let mut block = Block::new(&format!("match {}", compare));
+            block
+                .line(format!("{}({}::Equal) => {{}}", SOME, ORDERING))
+                .line("ordering => return ordering,");
+            function.push_block(block);
+        }
+    }
+    function
+}
+
+/// Common interface of the `codegen` containers that hold statements.
+trait Body {
+    fn line(&mut self, line: String);
+    fn block(&mut self, block: Block);
+}
+
+impl Body for Function {
+    fn line(&mut self, line: String) {
+        Function::line(self, line);
+    }
+
+    fn block(&mut self, block: Block) {
+        self.push_block(block);
+    }
+}
+
+impl Body for Block {
+    fn line(&mut self, line: String) {
+        Block::line(self, line);
+    }
+
+    fn block(&mut self, block: Block) {
+        self.push_block(block);
+    }
+}
+
+/// An expression that emitted code reads a value from.
+enum Value {
+    /// A place such as a struct field.
+    Place(String),
+    /// A reference to the value, such as a loop variable.
+    Ref(String),
+    /// A place holding an `Option`, with `None` standing for Java's null.
+    Nullable(String),
+}
+
+impl Value {
+    fn by_ref(&self) -> String {
+        match self {
+            Value::Place(place) => format!("&{}", place),
+            Value::Ref(reference) => reference.clone(),
+            Value::Nullable(_) => unreachable!("nullable values are not read directly"),
+        }
+    }
+
+    fn by_value(&self) -> String {
+        match self {
+            Value::Place(place) => place.clone(),
+            Value::Ref(reference) => format!("*{}", reference),
+            Value::Nullable(_) => unreachable!("nullable values are not read directly"),
+        }
+    }
+
+    /// The value as an `Option` of a reference, for archive methods that
+    /// accept Java's null.
+    fn by_option(&self) -> String {
+        match self {
+            Value::Place(place) => format!("{}(&{})", SOME, place),
+            Value::Ref(reference) => format!("{}({})", SOME, reference),
+            Value::Nullable(place) => format!("{}.as_deref()", place),
+        }
+    }
+
+    /// The expression to call methods on, relying on auto-deref.
+    fn receiver(&self) -> &str {
+        match self {
+            Value::Place(place) => place,
+            Value::Ref(reference) => reference,
+            Value::Nullable(_) => unreachable!("nullable values are not read directly"),
+        }
+    }
+}
+
+/// Emits the statements writing `value` of type `t` under `tag`. `depth`
+/// numbers the loop variables of nested containers the way Java does.
+fn write_value(
+    body: &mut dyn Body,
+    t: &FieldType,
+    value: &Value,
+    tag: &str,
+    depth: usize,
+    types: &Types,
+) {
+    match t {
+        FieldType::Primitive(primitive) => write_primitive(body, primitive, value, tag, types),
+        FieldType::Vector(element_type) => {
+            let elements = match value {
+                Value::Nullable(_) => "elements".to_string(),
+                _ => value.by_ref(),
+            };
+            let element_name = format!("e{}", depth);
+            let mut for_loop = Block::new(&format!("for {} in {}", element_name, elements));
+            write_value(
+                &mut for_loop,
+                element_type,
+                &Value::Ref(element_name.clone()),
+                &element_name,
+                depth + 1,
+                types,
+            );
+
+            if let Value::Nullable(place) = value {
+                body.line(format!(
+                    "archive.start_vector({}.as_ref().map({}::len), \"{}\")?;",
+                    place, VEC, tag
+                ));
+                let mut if_present =
+                    Block::new(&format!("if let {}({}) = &{}", SOME, elements, place));
+                if_present.push_block(for_loop);
+                body.block(if_present);
+            } else {
+                body.line(format!(
+                    "archive.start_vector({}({}.len()), \"{}\")?;",
+                    SOME,
+                    value.receiver(),
+                    tag
+                ));
+                body.block(for_loop);
+            }
+            body.line(format!("archive.end_vector(\"{}\")?;", tag));
+        }
+        FieldType::Map(key_type, value_type) => {
+            body.line(format!(
+                "archive.start_map({}.len(), \"{}\")?;",
+                value.receiver(),
+                tag
+            ));
+            let key_name = format!("k{}", depth);
+            let value_name = format!("v{}", depth);
+            let mut for_loop = Block::new(&format!(
+                "for ({}, {}) in {}",
+                key_name,
+                value_name,
+                value.by_ref()
+            ));
+            write_value(
+                &mut for_loop,
+                key_type,
+                &Value::Ref(key_name.clone()),
+                &key_name,
+                depth + 1,
+                types,
+            );
+            write_value(
+                &mut for_loop,
+                value_type,
+                &Value::Ref(value_name.clone()),
+                &value_name,
+                depth + 1,
+                types,
+            );
+            body.block(for_loop);
+            body.line(format!("archive.end_map(\"{}\")?;", tag));
+        }
+    }
+}
+
+fn write_primitive(
+    body: &mut dyn Body,
+    t: &PrimitiveFieldType,
+    value: &Value,
+    tag: &str,
+    types: &Types,
+) {
+    let (method, argument) = match t {
+        PrimitiveFieldType::Boolean => ("write_bool", value.by_value()),
+        PrimitiveFieldType::Byte if types.unsigned_bytes => {
+            ("write_byte", format!("{} as i8", value.by_value()))
+        }
+        PrimitiveFieldType::Byte => ("write_byte", value.by_value()),
+        PrimitiveFieldType::Int => ("write_int", value.by_value()),
+        PrimitiveFieldType::Long => ("write_long", value.by_value()),
+        PrimitiveFieldType::Float => ("write_float", value.by_value()),
+        PrimitiveFieldType::Double => ("write_double", value.by_value()),
+        PrimitiveFieldType::String => ("write_string", value.by_option()),
+        PrimitiveFieldType::Buffer => ("write_buffer", value.by_option()),
+        PrimitiveFieldType::Custom(_) => ("write_record", value.by_ref()),
+    };
+    let call = format!("{}({}, \"{}\")", method, argument, tag);
+    body.line(format!("archive.{}?;", call));
+}
+
+/// Emits the statements reading a value of type `t` into a new binding `name`.
+/// A `nullable` value is read into an `Option`, otherwise null reads back as
+/// an empty value.
+fn read_value(
+    body: &mut dyn Body,
+    t: &FieldType,
+    name: &str,
+    tag: &str,
+    depth: usize,
+    nullable: bool,
+    types: &Types,
+) {
+    match t {
+        FieldType::Primitive(PrimitiveFieldType::String) if nullable => {
+            body.line(format!("let {} = archive.read_string(\"{}\")?;", name, tag));
+        }
+        FieldType::Primitive(PrimitiveFieldType::Buffer) if nullable => {
+            body.line(format!("let {} = archive.read_buffer(\"{}\")?;", name, tag));
+        }
+        FieldType::Primitive(primitive) => {
+            body.line(format!(
+                "let {} = {};",
+                name,
+                read_primitive(primitive, tag, types)
+            ));
+        }
+        FieldType::Vector(element_type) => {
+            let length_name = format!("len{}", depth);
+            let element_name = format!("e{}", depth);
+            let mut if_present = Block::new(&format!(
+                "if let {}({}) = archive.start_vector(\"{}\")?",
+                SOME, length_name, tag
+            ));
+            if nullable {
+                body.line(format!("let mut {} = ::std::option::Option::None;", name));
+                if_present.line(format!("let {} = {}.insert({}::new());", name, name, VEC));
+            } else {
+                body.line(format!("let mut {} = {}::new();", name, VEC));
             }
+            let mut for_loop = Block::new(&format!("for _ in 0..{}", length_name));
+            read_value(
+                &mut for_loop,
+                element_type,
+                &element_name,
+                &element_name,
+                depth + 1,
+                false,
+                types,
+            );
+            for_loop.line(format!("{}.push({});", name, element_name));
+            if_present.push_block(for_loop);
+            body.block(if_present);
+            body.line(format!("archive.end_vector(\"{}\")?;", tag));
+        }
+        FieldType::Map(key_type, value_type) => {
+            let key_name = format!("k{}", depth);
+            let value_name = format!("v{}", depth);
+            body.line(format!(
+                "let mut {} = {}::new();",
+                name,
+                types.options.map_type.path()
+            ));
+            let mut for_loop = Block::new(&format!("for _ in 0..archive.start_map(\"{}\")?", tag));
+            read_value(
+                &mut for_loop,
+                key_type,
+                &key_name,
+                &key_name,
+                depth + 1,
+                false,
+                types,
+            );
+            read_value(
+                &mut for_loop,
+                value_type,
+                &value_name,
+                &value_name,
+                depth + 1,
+                false,
+                types,
+            );
+            for_loop.line(format!("{}.insert({}, {});", name, key_name, value_name));
+            body.block(for_loop);
+            body.line(format!("archive.end_map(\"{}\")?;", tag));
+        }
+    }
+}
+
+/// Returns the expression reading a single value of type `t`.
+fn read_primitive(t: &PrimitiveFieldType, tag: &str, types: &Types) -> String {
+    match t {
+        PrimitiveFieldType::Boolean => format!("archive.read_bool(\"{}\")?", tag),
+        PrimitiveFieldType::Byte if types.unsigned_bytes => {
+            format!("archive.read_byte(\"{}\")? as u8", tag)
+        }
+        PrimitiveFieldType::Byte => format!("archive.read_byte(\"{}\")?", tag),
+        PrimitiveFieldType::Int => format!("archive.read_int(\"{}\")?", tag),
+        PrimitiveFieldType::Long => format!("archive.read_long(\"{}\")?", tag),
+        PrimitiveFieldType::Float => format!("archive.read_float(\"{}\")?", tag),
+        PrimitiveFieldType::Double => format!("archive.read_double(\"{}\")?", tag),
+        PrimitiveFieldType::String => {
+            format!("archive.read_string(\"{}\")?.unwrap_or_default()", tag)
+        }
+        PrimitiveFieldType::Buffer => {
+            format!("archive.read_buffer(\"{}\")?.unwrap_or_default()", tag)
+        }
+        PrimitiveFieldType::Custom(reference) => {
+            let path = types.class_path(reference);
+            // The archive's type parameter `A` shadows a class `A` of the
+            // same module, which `self::` reaches all the same. Unresolved
+            // references name a Rust type as written, which may be local to
+            // a function that `self::` does not reach.
+            let is_local = reference.target.is_some() && !path.contains("::");
+            let path = if is_local {
+                format!("self::{}", path)
+            } else {
+                path
+            };
+            format!("archive.read_record::<{}>(\"{}\")?", path, tag)
         }
     }
+}
 
-    fn to_string(&self) -> String {
-        self.scope.to_string()
+/// Returns whether a Jute `byte` appears anywhere in `t`.
+fn contains_byte(t: &FieldType) -> bool {
+    match t {
+        FieldType::Primitive(primitive) => *primitive == PrimitiveFieldType::Byte,
+        FieldType::Vector(element_type) => contains_byte(element_type),
+        FieldType::Map(key_type, value_type) => {
+            contains_byte(key_type) || contains_byte(value_type)
+        }
     }
 }
 
@@ -94,32 +1508,1081 @@ fn jute_module_to_rust(name: String) -> String {
     str::replace(&name, ".", "_")
 }
 
-fn jute_to_rust_type(t: FieldType) -> String {
-    match t {
-        FieldType::Primitive(PrimitiveFieldType::Boolean) => String::from("bool"),
-        FieldType::Primitive(PrimitiveFieldType::Buffer) => String::from("Vec<u8>"),
-        FieldType::Primitive(PrimitiveFieldType::Byte) => String::from("u8"),
-        FieldType::Primitive(PrimitiveFieldType::Double) => String::from("f64"),
-        FieldType::Primitive(PrimitiveFieldType::Float) => String::from("f32"),
-        FieldType::Primitive(PrimitiveFieldType::Int) => String::from("i32"),
-        FieldType::Primitive(PrimitiveFieldType::Long) => String::from("i64"),
-        FieldType::Primitive(PrimitiveFieldType::String) => String::from("String"),
-        FieldType::Vector(t) => format!("Vec<{}>", jute_to_rust_type(FieldType::Primitive(t))),
-        FieldType::Primitive(PrimitiveFieldType::Custom(name)) => jute_type_reference_to_rust(name),
-        other => String::from("unknown"),
+/// Names Rust types as seen from one generated module.
+struct Types<'a> {
+    schema: &'a Schema,
+    /// Name of the Jute module.
+    jute_module: &'a str,
+    /// Path of the generated module below the generated code's root.
+    module: Vec<String>,
+    options: &'a Options,
+    /// Whether `byte` is `u8` rather than `i8`, which is set per field.
+    unsigned_bytes: bool,
+}
+
+impl<'a> Types<'a> {
+    /// Returns the types as seen from a field of `class`.
+    fn for_field(&self, class: &Class, field: &Field) -> Types<'a> {
+        let name = self.field_name(class, field);
+        Types {
+            module: self.module.clone(),
+            unsigned_bytes: self.options.unsigned_bytes.contains(&name),
+            ..*self
+        }
+    }
+
+    /// Returns the fully qualified Jute name of a field of `class`.
+    fn field_name(&self, class: &Class, field: &Field) -> String {
+        format!("{}.{}.{}", self.jute_module, class.name, field.name)
+    }
+
+    /// Returns the type of the struct field generated for a field of `class`.
+    fn field_type(&self, class: &Class, field: &Field) -> String {
+        let rust_type = self.for_field(class, field).rust_type(&field.field_type);
+        if self.is_nullable(class, field) {
+            format!("::std::option::Option<{}>", rust_type)
+        } else {
+            rust_type
+        }
+    }
+
+    fn is_nullable(&self, class: &Class, field: &Field) -> bool {
+        let can_be_null = matches!(
+            field.field_type,
+            FieldType::Primitive(PrimitiveFieldType::String)
+                | FieldType::Primitive(PrimitiveFieldType::Buffer)
+                | FieldType::Vector(_)
+        );
+        let name = self.field_name(class, field);
+        let nullable = self
+            .options
+            .nullable_fields
+            .iter()
+            .rev()
+            .find(|(field, _)| *field == name)
+            .map_or(
+                self.options.nullability == Nullability::Always,
+                |(_, nullable)| *nullable,
+            );
+        can_be_null && nullable
+    }
+
+    fn rust_type(&self, t: &FieldType) -> String {
+        match t {
+            FieldType::Primitive(PrimitiveFieldType::Boolean) => String::from("bool"),
+            FieldType::Primitive(PrimitiveFieldType::Buffer) => format!("{}<u8>", VEC),
+            FieldType::Primitive(PrimitiveFieldType::Byte) if self.unsigned_bytes => {
+                String::from("u8")
+            }
+            FieldType::Primitive(PrimitiveFieldType::Byte) => String::from("i8"),
+            FieldType::Primitive(PrimitiveFieldType::Double) => String::from("f64"),
+            FieldType::Primitive(PrimitiveFieldType::Float) => String::from("f32"),
+            FieldType::Primitive(PrimitiveFieldType::Int) => String::from("i32"),
+            FieldType::Primitive(PrimitiveFieldType::Long) => String::from("i64"),
+            FieldType::Primitive(PrimitiveFieldType::String) => STRING.to_string(),
+            FieldType::Vector(element_type) => {
+                format!("{}<{}>", VEC, self.rust_type(element_type))
+            }
+            FieldType::Map(key_type, value_type) => format!(
+                "{}<{}, {}>",
+                self.options.map_type.path(),
+                self.rust_type(key_type),
+                self.rust_type(value_type)
+            ),
+            FieldType::Primitive(PrimitiveFieldType::Custom(reference)) => {
+                self.class_path(reference)
+            }
+        }
+    }
+
+    /// Returns the path of the struct generated for the referenced class.
+    /// References without a target name the Rust type of a record outside
+    /// the schema, such as a `RecordStruct`.
+    fn class_path(&self, reference: &TypeReference) -> String {
+        let target = match reference.target {
+            Some(target) => target,
+            None => return reference.name.clone(),
+        };
+        let class = self.schema.class(target);
+        let module = self
+            .options
+            .module_path(&self.schema.modules[target.module].name);
+
+        let mut segments = vec![];
+        if let Some(root) = &self.options.root_module {
+            segments.push(root.to_string());
+            segments.extend(module);
+        } else {
+            let common = self
+                .module
+                .iter()
+                .zip(&module)
+                .take_while(|(a, b)| a == b)
+                .count();
+            segments.extend(vec!["super".to_string(); self.module.len() - common]);
+            segments.extend(module.into_iter().skip(common));
+        }
+        segments.push(rust_class_name(&class.name));
+        segments.join("::")
     }
 }
 
-fn jute_type_reference_to_rust(name: String) -> String {
-    if let Some(pos) = name.rfind('.') {
-        let (module_name, type_name) = name.split_at(pos);
-        let type_name_without_dot = &type_name[1..type_name.len()];
-        return format!(
-            "{}::{}",
-            jute_module_to_rust(String::from(module_name)),
-            type_name_without_dot
+impl Options {
+    /// Returns the path of the Rust module generated for a Jute module.
+    fn module_path(&self, name: &str) -> Vec<String> {
+        self.unsanitized_module_path(name)
+            .iter()
+            .map(|segment| sanitize(segment))
+            .collect()
+    }
+
+    /// Returns `module_path` before its segments are made valid identifiers.
+    fn unsanitized_module_path(&self, name: &str) -> Vec<String> {
+        let renamed = self
+            .module_renames
+            .iter()
+            .filter_map(|(from, to)| Some((from.len(), rename_module(name, from, to)?)))
+            .max_by_key(|(len, _)| *len)
+            .map_or_else(|| name.to_string(), |(_, renamed)| renamed);
+
+        if self.nested_modules {
+            renamed.split('.').map(str::to_string).collect()
+        } else {
+            vec![jute_module_to_rust(renamed)]
+        }
+    }
+}
+
+/// Replaces the prefix `from` of the module name `name` with `to`, if `from`
+/// is made of whole parts of `name`.
+fn rename_module(name: &str, from: &str, to: &str) -> Option<String> {
+    let rest = name.strip_prefix(from)?;
+    if rest.is_empty() {
+        // Stripping all of the name would leave no module at all.
+        Some(to.to_string()).filter(|to| !to.is_empty())
+    } else if to.is_empty() {
+        rest.strip_prefix('.').map(str::to_string)
+    } else {
+        rest.strip_prefix('.')
+            .map(|rest| format!("{}.{}", to, rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jute_rust_parser::Span;
+
+    fn generator() -> Generator {
+        generator_with(Options::default())
+    }
+
+    fn generator_with(options: Options) -> Generator {
+        Generator {
+            scope: Scope::new(),
+            options,
+        }
+    }
+
+    fn generate(text: &str) -> String {
+        generate_with(text, generator())
+    }
+
+    fn generate_with(text: &str, mut generator: Generator) -> String {
+        let mut schema = Parser::from_string(text).parse_all().unwrap();
+        schema.resolve().unwrap();
+        generator.generate(&schema);
+        generator.to_string()
+    }
+
+    #[test]
+    fn test_comments_become_docs() {
+        let code = generate(
+            "
+module a {
+    // information shared with the client
+    class Stat {
+        long czxid; // created zxid
+        /* spans
+         * two lines */
+        long mzxid;
+    }
+}",
+        );
+
+        assert!(code.contains(
+            "    /// information shared with the client
+    #[derive(Debug)]
+    pub struct Stat {
+        /// created zxid
+        pub czxid: i64,
+        /// spans
+        /// two lines
+        pub mzxid: i64,
+    }"
+        ));
+    }
+
+    #[test]
+    fn test_map_type() {
+        let text = "module a { class B { map<ustring, int> counts; } }";
+
+        let code = generate(text);
+        assert!(
+            code.contains("pub counts: ::std::collections::BTreeMap<::std::string::String, i32>,")
+        );
+        assert!(code.contains("let mut counts = ::std::collections::BTreeMap::new();"));
+
+        let code = generate_with(
+            text,
+            generator_with(Options {
+                map_type: MapType::HashMap,
+                ..Options::default()
+            }),
+        );
+        assert!(
+            code.contains("pub counts: ::std::collections::HashMap<::std::string::String, i32>,")
         );
+        assert!(code.contains("let mut counts = ::std::collections::HashMap::new();"));
     }
 
-    name
+    #[test]
+    fn test_nested_types() {
+        let code = generate_with(
+            "module a.b { class C {
+                vector<vector<long>> matrix;
+                map<ustring, vector<a.c.D>> ds;
+            } }
+            module a.c { class D {} }",
+            generator_with(Options {
+                map_type: MapType::HashMap,
+                ..Options::default()
+            }),
+        );
+
+        assert!(code.contains("pub matrix: ::std::vec::Vec<::std::vec::Vec<i64>>,"));
+        assert!(code.contains(
+            "pub ds: ::std::collections::HashMap<::std::string::String, \
+             ::std::vec::Vec<super::a_c::D>>,"
+        ));
+    }
+
+    #[test]
+    fn test_type_paths() {
+        let text = "
+module a.data { class Id {} class ACL { Id id; } }
+module a.proto { class Create { vector<a.data.ACL> acl; a.proto.Header header; } class Header {} }";
+
+        let code = generate(text);
+        assert!(code.contains("pub id: Id,"));
+        assert!(code.contains("pub acl: ::std::vec::Vec<super::a_data::ACL>,"));
+        assert!(code.contains("archive.read_record::<super::a_data::ACL>(\"e1\")?"));
+        assert!(code.contains("pub header: Header,"));
+
+        let code = generate_with(
+            text,
+            generator_with(Options {
+                root_module: Some("crate::generated".to_string()),
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains("pub id: crate::generated::a_data::Id,"));
+        assert!(code.contains("pub acl: ::std::vec::Vec<crate::generated::a_data::ACL>,"));
+        assert!(code.contains("pub header: crate::generated::a_proto::Header,"));
+    }
+
+    #[test]
+    fn test_run_reports_every_error() {
+        let dir = env::temp_dir().join(format!("jute-rust-codegen-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        fs::write(path("good.jute"), "module a { class B { int x; } }").unwrap();
+        fs::write(
+            path("bad.jute"),
+            "module a {\n  class C { int; }\n}\nmodule b { class D { x } }",
+        )
+        .unwrap();
+
+        let error = Runner::new()
+            .add_file(path("good.jute"))
+            .add_file(path("bad.jute"))
+            .add_file(path("missing.jute"))
+            .set_output(path("generated.rs"))
+            .run()
+            .unwrap_err();
+
+        let files: Vec<&str> = error.diagnostics().iter().map(Diagnostic::file).collect();
+        assert_eq!(
+            files,
+            vec![
+                path("bad.jute").as_str(),
+                path("bad.jute").as_str(),
+                path("missing.jute").as_str()
+            ]
+        );
+        assert!(error.to_string().starts_with(&format!(
+            "{}:2:16: expected field name, found `;`\n{}:4:24: expected field name, found `}}`\n",
+            path("bad.jute"),
+            path("bad.jute")
+        )));
+        assert!(!dir.join("generated.rs").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_reports_unknown_types() {
+        let dir = env::temp_dir().join(format!("jute-rust-codegen-resolve-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        fs::write(path("a.jute"), "module a { class B { int x; } }").unwrap();
+        fs::write(path("c.jute"), "module c {\n  class D { a.B b; a.C c; }\n}").unwrap();
+
+        let error = Runner::new()
+            .add_file(path("a.jute"))
+            .add_file(path("c.jute"))
+            .set_output(path("generated.rs"))
+            .run()
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            format!("{}:2:20: unknown type `a.C`", path("c.jute"))
+        );
+        assert!(!dir.join("generated.rs").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = env::temp_dir().join(format!("jute-rust-codegen-include-{}", std::process::id()));
+        fs::create_dir_all(dir.join("data")).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        fs::write(
+            path("proto.jute"),
+            "include \"data/data.jute\"\nmodule proto { class Request { data.Id id; } }",
+        )
+        .unwrap();
+        fs::write(
+            path("data/data.jute"),
+            "include \"../proto.jute\"\ninclude \"missing.jute\"\nmodule data { class Id { ustring id; } }",
+        )
+        .unwrap();
+
+        let mut runner = Runner::new();
+        runner.add_file(path("proto.jute"));
+        // Each file is read once, even when includes form a cycle.
+        let (result, files) = runner.generate_tracked();
+        assert_eq!(
+            files,
+            vec![
+                path("proto.jute"),
+                path("data/data.jute"),
+                "missing.jute".to_string(),
+            ]
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "missing.jute: No such file or directory (os error 2)"
+        );
+
+        fs::write(
+            path("data/data.jute"),
+            "module data { class Id { ustring id; } }",
+        )
+        .unwrap();
+        let (result, files) = runner.generate_tracked();
+        assert_eq!(files.len(), 2);
+        let code = result.unwrap();
+        assert!(code.contains("pub mod data {"));
+        assert!(code.contains("pub id: super::data::Id,"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_generate_sources() {
+        let code = Runner::new()
+            .add_source(
+                "a".to_string(),
+                "module a { class B { int x; } }".to_string(),
+            )
+            .add_source(
+                "c".to_string(),
+                "module c { class D { a.B b; } }".to_string(),
+            )
+            .generate()
+            .unwrap();
+        assert!(code.contains("pub b: super::a::B,"));
+
+        let error = Runner::new()
+            .add_source(
+                "a".to_string(),
+                "module a {\n  class B { a.C c; }\n}".to_string(),
+            )
+            .generate()
+            .unwrap_err();
+        let diagnostic = &error.diagnostics()[0];
+        assert_eq!(diagnostic.file(), "a");
+        let span = diagnostic.span().unwrap();
+        assert_eq!((span.start, span.line, span.column), (23, 2, 13));
+        assert_eq!(error.to_string(), "a:2:13: unknown type `a.C`");
+    }
+
+    #[test]
+    fn test_map_keys() {
+        let text = "module m {
+  class K { int id; }
+  class P { float x; }
+  class B { map<double, int> d; vector<map<m.K, int>> k; map<ustring, map<m.P, int>> p; }
+}";
+        let messages = |runner: &mut Runner| {
+            let error = runner
+                .add_source("m".to_string(), text.to_string())
+                .generate()
+                .unwrap_err();
+            let messages: Vec<String> = error
+                .diagnostics()
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            messages
+        };
+
+        assert_eq!(
+            messages(&mut Runner::new()),
+            [
+                "m:4:13: the map keys of field `d` in class `m.B` do not implement `Ord`, which `BTreeMap` needs",
+                "m:4:33: the map keys of field `k` in class `m.B` do not implement `Ord`, which `BTreeMap` needs",
+                "m:4:58: the map keys of field `p` in class `m.B` do not implement `Ord`, which `BTreeMap` needs",
+            ]
+        );
+        assert_eq!(
+            messages(Runner::new().set_map_type(MapType::HashMap)),
+            [
+                "m:4:13: the map keys of field `d` in class `m.B` do not implement `Eq` and `Hash`, which `HashMap` needs",
+                "m:4:33: the map keys of field `k` in class `m.B` do not implement `Eq` and `Hash`, which `HashMap` needs",
+                "m:4:58: the map keys of field `p` in class `m.B` do not implement `Eq` and `Hash`, which `HashMap` needs",
+            ]
+        );
+
+        // Record keys work once they implement the traits, which float
+        // fields rule out.
+        let derives = ["Debug", "PartialEq", "Eq", "PartialOrd", "Ord", "Hash"];
+        let mut runner = Runner::new();
+        runner.set_derives(derives.iter().map(ToString::to_string).collect());
+        let messages = messages(&mut runner);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("field `d`"));
+        assert!(messages[1].contains("field `p`"));
+
+        let code = Runner::new()
+            .set_derives(derives.iter().map(ToString::to_string).collect())
+            .set_map_type(MapType::HashMap)
+            .add_source(
+                "m".to_string(),
+                "module m { class K { int id; } class B { map<m.K, int> k; } }".to_string(),
+            )
+            .generate()
+            .unwrap();
+        assert!(code.contains("pub k: ::std::collections::HashMap<K, i32>,"));
+    }
+
+    #[test]
+    fn test_module_paths() {
+        let options = Options {
+            module_renames: vec![
+                ("org.apache".to_string(), "apache".to_string()),
+                ("org.apache.zookeeper".to_string(), "zk".to_string()),
+                ("com.example".to_string(), "".to_string()),
+            ],
+            ..Options::default()
+        };
+        assert_eq!(
+            options.module_path("org.apache.zookeeper.proto"),
+            vec!["zk_proto"]
+        );
+        assert_eq!(
+            options.module_path("org.apache.zookeeperx"),
+            vec!["apache_zookeeperx"]
+        );
+        assert_eq!(options.module_path("org.apache"), vec!["apache"]);
+        assert_eq!(options.module_path("com.example.data"), vec!["data"]);
+        assert_eq!(options.module_path("com.example"), vec!["com_example"]);
+
+        let options = Options {
+            nested_modules: true,
+            ..options
+        };
+        assert_eq!(
+            options.module_path("org.apache.zookeeper.proto"),
+            vec!["zk", "proto"]
+        );
+        assert_eq!(options.module_path("net.a.b"), vec!["net", "a", "b"]);
+    }
+
+    #[test]
+    fn test_nested_modules() {
+        let code = generate_with(
+            "module org.apache.zookeeper.data { class Stat {} }
+            module org.apache.zookeeper.proto { class Response { org.apache.zookeeper.data.Stat stat; } }
+            module org.apache.jute { class Record { org.apache.zookeeper.data.Stat stat; } }",
+            generator_with(Options {
+                nested_modules: true,
+                module_renames: vec![("org.apache.zookeeper".to_string(), "zk".to_string())],
+                ..Options::default()
+            }),
+        );
+
+        assert!(code.starts_with("pub mod zk {\n    pub mod data {\n"));
+        assert!(code.contains("pub stat: super::data::Stat,"));
+        assert!(code.contains("pub mod org {\n    pub mod apache {\n        pub mod jute {\n"));
+        assert!(code.contains("pub stat: super::super::super::zk::data::Stat,"));
+    }
+
+    #[test]
+    fn test_field_names() {
+        assert_eq!(rust_field_name("czxid"), "czxid");
+        assert_eq!(rust_field_name("lastZxidSeen"), "last_zxid_seen");
+        assert_eq!(rust_field_name("ephemeralOwner"), "ephemeral_owner");
+        assert_eq!(rust_field_name("ACL"), "acl");
+        assert_eq!(rust_field_name("requestURLFormat"), "request_url_format");
+        assert_eq!(rust_field_name("v2Watch"), "v2_watch");
+        assert_eq!(rust_field_name("type"), "r#type");
+        assert_eq!(rust_field_name("Match"), "r#match");
+
+        let code = generate("module a { class B { int lastZxidSeen; long type; } }");
+        assert!(code.contains("pub last_zxid_seen: i32,"));
+        assert!(code.contains("pub r#type: i64,"));
+        assert!(code.contains("archive.write_int(self.last_zxid_seen, \"lastZxidSeen\")?;"));
+        assert!(code.contains("let r#type = archive.read_long(\"type\")?;"));
+
+        let code = generate("module a { class Foo { int fooBar; int foo_bar; long foo_bar_1; } }");
+        assert!(code.contains("pub foo_bar: i32,"));
+        assert!(code.contains(
+            "/// Named `foo_bar` in the Jute schema.
+        pub foo_bar_2: i32,"
+        ));
+        assert!(code.contains("pub foo_bar_1: i64,"));
+        assert!(code.contains("archive.write_int(self.foo_bar_2, \"foo_bar\")?;"));
+        assert!(code.contains("let foo_bar_2 = archive.read_int(\"foo_bar\")?;"));
+    }
+
+    #[test]
+    fn test_derives() {
+        let text = "
+module a {
+    class Id { ustring id; }
+    class Point { double x; }
+    class Shape { vector<a.Point> points; a.Id id; }
+    class Tree { vector<a.Tree> children; map<int, a.Id> ids; }
+}";
+        let derives = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+
+        let code = generate_with(
+            text,
+            generator_with(Options {
+                derives: derives(&["Debug", "Clone", "PartialEq", "Eq", "std::hash::Hash"]),
+                class_derives: vec![("a.Id".to_string(), derives(&["Debug", "Copy", "Clone"]))],
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains("#[derive(Debug, Clone)]\n    pub struct Id {"));
+        assert!(code.contains("#[derive(Debug, Clone, PartialEq)]\n    pub struct Point {"));
+        assert!(code.contains("#[derive(Debug, Clone)]\n    pub struct Shape {"));
+        assert!(code.contains("#[derive(Debug, Clone)]\n    pub struct Tree {"));
+
+        let code = generate_with(
+            text,
+            generator_with(Options {
+                map_type: MapType::HashMap,
+                derives: derives(&["Clone", "PartialEq", "Eq", "Hash", "serde::Serialize"]),
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains(
+            "#[derive(Clone, PartialEq, Eq, Hash, serde::Serialize)]\n    pub struct Id {"
+        ));
+        assert!(
+            code.contains("#[derive(Clone, PartialEq, serde::Serialize)]\n    pub struct Point {")
+        );
+        assert!(code
+            .contains("#[derive(Clone, PartialEq, Eq, serde::Serialize)]\n    pub struct Tree {"));
+
+        // Serde sees the Jute names too.
+        let text = "module a { class B { long lastZxid; int type; int self; int id; } }";
+        let code = generate_with(
+            text,
+            generator_with(Options {
+                derives: derives(&["Debug", "Deserialize"]),
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains(
+            "        #[serde(rename = \"lastZxid\")]
+        pub last_zxid: i64,
+        pub r#type: i32,
+        /// Named `self` in the Jute schema.
+        #[serde(rename = \"self\")]
+        pub self_: i32,
+        pub id: i32,"
+        ));
+        assert!(!generate(text).contains("serde"));
+    }
+
+    #[test]
+    fn test_java_ordering() {
+        let code = generate_with(
+            "module a {
+                class Id { ustring id; boolean watch; buffer data; }
+                class Point { double x; a.Id id; }
+                class List { vector<a.Id> ids; }
+                class Wrapper { a.List list; }
+            }",
+            generator_with(Options {
+                derives: ["Debug", "PartialEq", "Eq", "PartialOrd", "Ord"]
+                    .iter()
+                    .map(|derive| derive.to_string())
+                    .collect(),
+                ..Options::default()
+            }),
+        );
+
+        assert!(code.contains("#[derive(Debug, PartialEq, Eq)]\n    pub struct Id {"));
+        assert!(code.contains(
+            "result = result.wrapping_mul(37).wrapping_add(\
+             ::jute_rust_runtime::JavaHashCode::java_hash_code(&self.id));"
+        ));
+        assert!(code.contains(
+            "result = result.wrapping_mul(37).wrapping_add(if self.watch { 0 } else { 1 });"
+        ));
+        assert!(code.contains(
+            "result = result.wrapping_mul(37).wrapping_add(\
+             ::jute_rust_runtime::buffer_hash_code(::std::option::Option::Some(&self.data)));"
+        ));
+        assert!(code.contains(
+            "::jute_rust_runtime::JavaComparable::compare_to(&self.id, &other.id)
+                .then_with(|| ::jute_rust_runtime::JavaComparable::compare_to(&self.watch, &other.watch))"
+        ));
+        assert!(code.contains("impl ::std::cmp::Ord for Id {"));
+        assert!(code.contains("::std::option::Option::Some(self.cmp(other))"));
+
+        // Without `Eq`, only `PartialOrd` is implemented, leaving NaN
+        // unordered.
+        assert!(code.contains("#[derive(Debug, PartialEq)]\n    pub struct Point {"));
+        assert!(code.contains(
+            "    impl ::std::cmp::PartialOrd for Point {
+        fn partial_cmp(&self, other: &Self) -> ::std::option::Option<::std::cmp::Ordering> {
+            match ::std::cmp::PartialOrd::partial_cmp(&self.x, &other.x) {
+                ::std::option::Option::Some(::std::cmp::Ordering::Equal) => {}
+                ordering => return ordering,
+            }
+            ::std::cmp::PartialOrd::partial_cmp(&self.id, &other.id)
+        }
+    }"
+        ));
+        assert!(!code.contains("impl ::std::cmp::Ord for Point {"));
+
+        // Java cannot compare vectors, so these keep the derived ordering.
+        assert!(code
+            .contains("#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]\n    pub struct List {"));
+        assert!(code.contains(
+            "#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]\n    pub struct Wrapper {"
+        ));
+        assert!(!code.contains("JavaComparable for List"));
+        assert!(code.contains("impl ::jute_rust_runtime::JavaHashCode for Wrapper {"));
+
+        // Ordering is only implemented when it is asked for.
+        let code = generate_with(
+            "module a { class Id { ustring id; } }",
+            generator_with(Options {
+                derives: ["Debug", "PartialEq", "Eq"]
+                    .iter()
+                    .map(|derive| derive.to_string())
+                    .collect(),
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains("impl ::jute_rust_runtime::JavaComparable for Id {"));
+        assert!(!code.contains("PartialOrd"));
+        assert!(!code.contains("impl ::std::cmp::Ord for"));
+
+        // A class without fields never looks at the other record.
+        let code = generate_with(
+            "module a { class Empty { } }",
+            generator_with(Options {
+                derives: ["PartialEq", "PartialOrd"]
+                    .iter()
+                    .map(|derive| derive.to_string())
+                    .collect(),
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains("fn compare_to(&self, _other: &Self) -> ::std::cmp::Ordering {"));
+        assert!(code.contains(
+            "fn partial_cmp(&self, _other: &Self) -> ::std::option::Option<::std::cmp::Ordering> {"
+        ));
+    }
+
+    #[test]
+    fn test_record_struct() {
+        let field = |name: &str, field_type: FieldType| StructField {
+            name: name.to_string(),
+            field_type,
+            nullable: false,
+            unsigned_bytes: false,
+            map_type: MapType::BTreeMap,
+            converted: false,
+        };
+        let code = RecordStruct {
+            name: "Counts".to_string(),
+            fields: vec![
+                StructField {
+                    map_type: MapType::HashMap,
+                    unsigned_bytes: true,
+                    ..field(
+                        "r#type",
+                        FieldType::map(
+                            FieldType::Primitive(PrimitiveFieldType::Byte),
+                            FieldType::Primitive(PrimitiveFieldType::Custom(TypeReference {
+                                name: "super::Id".to_string(),
+                                span: Span::default(),
+                                target: None,
+                            })),
+                        ),
+                    )
+                },
+                StructField {
+                    converted: true,
+                    ..field("zxid", FieldType::Primitive(PrimitiveFieldType::Long))
+                },
+            ],
+        }
+        .generate();
+
+        assert!(code.contains("impl ::jute_rust_runtime::Record for Counts {"));
+        assert!(code.contains("archive.write_byte(*k1 as i8, \"k1\")?;"));
+        assert!(code.contains("let mut r#type = ::std::collections::HashMap::new();"));
+        assert!(code.contains("let v1 = archive.read_record::<super::Id>(\"v1\")?;"));
+        assert!(code.contains(
+            "let self_zxid: i64 = ::std::convert::From::from(::std::clone::Clone::clone(&self.zxid));"
+        ));
+        assert!(code.contains("archive.write_long(self_zxid, \"zxid\")?;"));
+        assert!(code.contains("let zxid = ::std::convert::From::from(zxid);"));
+        assert!(code.contains("impl ::jute_rust_runtime::JavaHashCode for Counts {"));
+        assert!(!code.contains("JavaComparable"));
+        assert!(!code.contains("SIGNATURE"));
+        assert!(code.contains(
+            "        signature.push_str(\"({b\");
+        <super::Id as ::jute_rust_runtime::JavaSignature>::write_signature(signature, enclosing);
+        signature.push_str(\"}l)\");"
+        ));
+    }
+
+    #[test]
+    fn test_signatures() {
+        let mut schema = Parser::from_string(
+            "module a {
+                class Id { ustring scheme; ustring id; }
+                class All { boolean z; byte b; int i; long l; float f; double d; buffer x; }
+                class Nested { vector<vector<a.Id>> ids; map<int, map<ustring, buffer>> m; }
+                class Tree { a.Id id; vector<a.Tree> children; }
+            }",
+        )
+        .parse_all()
+        .unwrap();
+        schema.resolve().unwrap();
+
+        let signatures: Vec<String> = schema.modules[0]
+            .classes
+            .iter()
+            .map(|class| signature::class_signature(&schema, class))
+            .collect();
+        assert_eq!(
+            signatures,
+            vec![
+                "LId(ss)",
+                "LAll(zbilfdB)",
+                "LNested([[LId(ss)]]{i{sB}})",
+                "LTree(LId(ss)[LTree])"
+            ]
+        );
+
+        let code = generate("module a { class Id { ustring id; } }");
+        assert!(code.contains(
+            "    impl Id {
+        /// The signature Java's Jute compiler gives this record, which
+        /// its peers compare to detect schema mismatches.
+        pub const SIGNATURE: &'static str = \"LId(s)\";
+    }"
+        ));
+        assert!(code.contains(
+            "    impl ::jute_rust_runtime::JavaSignature for Id {
+        fn write_signature(signature: &mut ::std::string::String, _enclosing: &mut ::std::vec::Vec<&'static str>) {
+            signature.push_str(Self::SIGNATURE);
+        }
+    }"
+        ));
+    }
+
+    #[test]
+    fn test_builders() {
+        let text = "module a { class B {
+            // the id
+            int id;
+            vector<ustring> names;
+            a.C c;
+        } class C { long type; } }";
+        assert!(!generate(text).contains("Builder"));
+
+        let code = generate_with(
+            text,
+            generator_with(Options {
+                builders: true,
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains(
+            "    impl B {
+        pub fn builder() -> BBuilder {
+            BBuilder::default()
+        }
+    }
+
+    /// Builds a [`B`].
+    #[derive(Default)]
+    pub struct BBuilder {
+        id: ::std::option::Option<i32>,
+        names: ::std::option::Option<::std::vec::Vec<::std::string::String>>,
+        c: ::std::option::Option<C>,
+    }"
+        ));
+        assert!(code.contains(
+            "        /// the id
+        pub fn id(self, value: i32) -> Self {
+            Self { id: ::std::option::Option::Some(value), ..self }
+        }"
+        ));
+        assert!(code.contains("id: self.id.unwrap_or_default(),"));
+        assert!(code.contains("names: self.names.unwrap_or_default(),"));
+        assert!(code.contains(
+            "c: self.c.ok_or(::jute_rust_runtime::BuildError::MissingField \
+             { record: \"B\", field: \"c\" })?,"
+        ));
+        assert!(code.contains(
+            "pub fn r#type(self, value: i64) -> Self {
+            Self { r#type: ::std::option::Option::Some(value) }
+        }"
+        ));
+
+        let code = generate_with(
+            "module a {
+                class Foo { int build; int default; int set_build; }
+                class FooBuilder { int x; }
+            }",
+            generator_with(Options {
+                builders: true,
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains("pub fn builder() -> FooBuilder1 {"));
+        assert!(code.contains(
+            "    /// Builds a [`Foo`].\n    /// \n    \
+             /// Named `FooBuilder1` because `FooBuilder` is taken.\n    \
+             #[derive(Default)]\n    pub struct FooBuilder1 {"
+        ));
+        assert!(code.contains("pub struct FooBuilder {"));
+        assert!(code.contains("pub fn builder() -> FooBuilderBuilder {"));
+        assert!(code.contains(
+            "/// Sets `build`, whose name is taken by another method.
+        pub fn set_set_build(self, value: i32) -> Self {
+            Self { build: ::std::option::Option::Some(value), ..self }
+        }"
+        ));
+        assert!(code.contains("pub fn set_default(self, value: i32) -> Self {"));
+        assert!(code.contains(
+            "pub fn set_build(self, value: i32) -> Self {
+            Self { set_build: ::std::option::Option::Some(value), ..self }
+        }"
+        ));
+        assert!(code.contains("pub fn build(self) -> ::std::result::Result<Foo,"));
+
+        // Without fields the builder is a unit struct.
+        let code = generate_with(
+            "module a { class Empty { } }",
+            generator_with(Options {
+                builders: true,
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains(
+            "pub fn builder() -> EmptyBuilder {
+            EmptyBuilder
+        }"
+        ));
+    }
+
+    #[test]
+    fn test_nullability() {
+        let text = "module a { class B { ustring s; buffer b; vector<int> v; int i; } }";
+        let code = generate(text);
+        assert!(code.contains("pub s: ::std::string::String,"));
+        assert!(code.contains("let s = archive.read_string(\"s\")?.unwrap_or_default();"));
+
+        let code = generate_with(
+            text,
+            generator_with(Options {
+                nullability: Nullability::Always,
+                nullable_fields: vec![("a.B.v".to_string(), false), ("a.B.i".to_string(), true)],
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains("pub s: ::std::option::Option<::std::string::String>,"));
+        assert!(code.contains("pub b: ::std::option::Option<::std::vec::Vec<u8>>,"));
+        assert!(code.contains("pub v: ::std::vec::Vec<i32>,"));
+        assert!(code.contains("pub i: i32,"));
+        assert!(code.contains("archive.write_string(self.s.as_deref(), \"s\")?;"));
+        assert!(code.contains("archive.write_buffer(self.b.as_deref(), \"b\")?;"));
+        assert!(code.contains("let s = archive.read_string(\"s\")?;"));
+        assert!(code.contains("let b = archive.read_buffer(\"b\")?;"));
+        assert!(code.contains("::jute_rust_runtime::buffer_hash_code(self.b.as_deref())"));
+
+        let code = generate_with(
+            text,
+            generator_with(Options {
+                nullable_fields: vec![("a.B.v".to_string(), true)],
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains("pub v: ::std::option::Option<::std::vec::Vec<i32>>,"));
+        assert!(code.contains(
+            "archive.start_vector(self.v.as_ref().map(::std::vec::Vec::len), \"v\")?;
+            if let ::std::option::Option::Some(elements) = &self.v {
+                for e1 in elements {"
+        ));
+        assert!(code.contains(
+            "let mut v = ::std::option::Option::None;
+            if let ::std::option::Option::Some(len1) = archive.start_vector(\"v\")? {
+                let v = v.insert(::std::vec::Vec::new());"
+        ));
+    }
+
+    #[test]
+    fn test_sanitized_names() {
+        assert_eq!(rust_field_name("self"), "self_");
+        assert_eq!(rust_field_name("Self"), "self_");
+        assert_eq!(rust_field_name("crate"), "crate_");
+        assert_eq!(rust_class_name("Self"), "Self_");
+        assert_eq!(rust_class_name("match"), "r#match");
+        assert_eq!(rust_class_name("i32"), "i32_");
+        assert_eq!(rust_class_name("String"), "String");
+
+        let code = generate(
+            "module type {
+                class String { ustring self; vector<int> archive; }
+                class Option { String value; int e1; }
+            }",
+        );
+        assert!(code.contains("pub mod r#type {"));
+        // Raw identifiers keep the name.
+        assert!(!code.contains("//! Named"));
+        assert!(code.contains(
+            "    /// Named `self` in the Jute schema.
+        pub self_: ::std::string::String,
+        pub archive: ::std::vec::Vec<i32>,"
+        ));
+        assert!(code.contains("pub value: String,"));
+        assert!(code.contains(
+            "archive.write_string(::std::option::Option::Some(&self.self_), \"self\")?;"
+        ));
+        assert!(code.contains("let mut archive_ = ::std::vec::Vec::new();"));
+        assert!(code.contains("archive: archive_,"));
+        assert!(code.contains("let e1_ = archive.read_int(\"e1\")?;"));
+        assert!(code.contains("::std::result::Result::Ok(Self {"));
+
+        let code = generate_with(
+            "module a.self { class Self { int crate; byte b; } class B { a.self.Self s; } }",
+            generator_with(Options {
+                nested_modules: true,
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains(
+            "    pub mod self_ {
+        //! Named `self` in the Jute schema.
+        //!
+        //! Jute's `byte` is Java's signed `byte`"
+        ));
+        assert!(code.contains("/// Named `Self` in the Jute schema.\n        #[derive(Debug)]\n        pub struct Self_ {"));
+        assert!(code.contains("impl ::jute_rust_runtime::Record for Self_ {"));
+        assert!(code.contains("pub s: Self_,"));
+
+        let code = generate("module m { class A { int x; } class B { A a; vector<A> as; } }");
+        assert!(code.contains("let a = archive.read_record::<self::A>(\"a\")?;"));
+        assert!(code.contains("let e1 = archive.read_record::<self::A>(\"e1\")?;"));
+
+        let code = generate(
+            "module m { class X { vector<int> archive; int archive_; int tag_; int tag; } }",
+        );
+        assert!(code.contains("let mut archive__ = ::std::vec::Vec::new();"));
+        assert!(code.contains("let archive_ = archive.read_int(\"archive_\")?;"));
+        assert!(code.contains("let tag_ = archive.read_int(\"tag_\")?;"));
+        assert!(code.contains("let tag__ = archive.read_int(\"tag\")?;"));
+        assert!(code.contains(
+            "archive: archive__,
+                archive_,
+                tag_,
+                tag: tag__,"
+        ));
+    }
+
+    #[test]
+    fn test_bytes() {
+        let text = "module a.b { class C { byte s; byte u; vector<byte> v; } } module d { class E { int i; } }";
+        let code = generate_with(
+            text,
+            generator_with(Options {
+                nested_modules: true,
+                unsigned_bytes: vec!["a.b.C.u".to_string(), "a.b.C.v".to_string()],
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains("pub s: i8,"));
+        assert!(code.contains("pub u: u8,"));
+        assert!(code.contains("pub v: ::std::vec::Vec<u8>,"));
+        assert!(code.contains("archive.write_byte(self.s, \"s\")?;"));
+        assert!(code.contains("archive.write_byte(self.u as i8, \"u\")?;"));
+        assert!(code.contains("archive.write_byte(*e1 as i8, \"e1\")?;"));
+        assert!(code.contains("let s = archive.read_byte(\"s\")?;"));
+        assert!(code.contains("let u = archive.read_byte(\"u\")? as u8;"));
+        assert!(code.contains("let e1 = archive.read_byte(\"e1\")? as u8;"));
+
+        // Only the modules with `byte` fields document them.
+        assert!(code.contains("pub mod b {\n        //! Jute's `byte` is Java's signed `byte`"));
+        assert_eq!(code.matches("Jute's `byte`").count(), 1);
+
+        // Later modules inside a documented one keep its note, and modules
+        // created as parents first still get theirs before any item.
+        let text = "module a.b.c { class D { byte y; } }
+            module a.b { class C { byte x; } }
+            module a.b.c.d { class E { int i; } }
+            module f { class G { byte z; } }
+            module f { class H { byte w; } }";
+        let code = generate_with(
+            text,
+            generator_with(Options {
+                nested_modules: true,
+                ..Options::default()
+            }),
+        );
+        assert!(code.contains("pub mod b {\n        //! Jute's `byte` is Java's signed `byte`"));
+        assert!(code.contains("pub mod c {\n            //! Jute's `byte` is Java's signed `byte`"));
+        assert!(code.contains("pub mod f {\n    //! Jute's `byte` is Java's signed `byte`"));
+        assert!(code.contains("pub mod d {\n                #[derive(Debug)]"));
+        assert_eq!(code.matches("Jute's `byte`").count(), 3);
+    }
 }
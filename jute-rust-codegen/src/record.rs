@@ -0,0 +1,203 @@
+use codegen::{Block, Function, Scope};
+use jute_rust_parser::{Class, Comments, Field, FieldType, PrimitiveFieldType, Schema, Span};
+
+use crate::{
+    generate_compare_to, generate_deserialize, generate_java_hash_code, generate_serialize,
+    generate_write_signature, signature, unraw, MapType, Options, RecordField, Types, RUNTIME,
+};
+
+/// A Rust struct written by hand rather than generated, described field by
+/// field, to implement the traits of a Jute record for. `jute-rust-macros`
+/// derives `JuteRecord` with it.
+pub struct RecordStruct {
+    /// The name of the struct.
+    pub name: String,
+    pub fields: Vec<StructField>,
+}
+
+/// A field of a `RecordStruct`.
+pub struct StructField {
+    /// The name of the field, which archives see without `r#` as tag.
+    pub name: String,
+    /// The Jute type of the field. Classes in it are not resolved, but name
+    /// the Rust type of the record instead.
+    pub field_type: FieldType,
+    /// Whether the field is an `Option`, with `None` written as Java's null.
+    pub nullable: bool,
+    /// Whether the bytes of the field are `u8` rather than `i8`.
+    pub unsigned_bytes: bool,
+    /// The collection the maps of the field are.
+    pub map_type: MapType,
+    /// Whether the field has a type of its own, which converts with `From`
+    /// to and from the Rust type of `field_type`.
+    pub converted: bool,
+}
+
+impl RecordStruct {
+    /// Returns the implementations of `Record`, `JavaHashCode` and
+    /// `JavaSignature` for the struct, the same as for a generated struct with
+    /// the same fields, and of `JavaComparable` unless a field is a vector or
+    /// map, which Java cannot compare. Nested records have to implement these
+    /// traits as well.
+    ///
+    /// Only structs without nested records have the `SIGNATURE` constant of
+    /// generated structs, since the signatures of nested records are not
+    /// known until they are written.
+    pub fn generate(&self) -> String {
+        let schema = Schema::default();
+        let options = |map_type| Options {
+            map_type,
+            ..Options::default()
+        };
+        let (btree_map, hash_map) = (options(MapType::BTreeMap), options(MapType::HashMap));
+        let class = Class {
+            name: unraw(&self.name).to_string(),
+            fields: self
+                .fields
+                .iter()
+                .map(|field| Field {
+                    name: unraw(&field.name).to_string(),
+                    field_type: field.field_type.clone(),
+                    comments: Comments::default(),
+                    span: Span::default(),
+                })
+                .collect(),
+            comments: Comments::default(),
+            span: Span::default(),
+        };
+        let fields: Vec<RecordField> = self
+            .fields
+            .iter()
+            .zip(&class.fields)
+            .map(|(field, jute_field)| RecordField {
+                field: jute_field,
+                name: field.name.clone(),
+                nullable: field.nullable,
+                converted: field.converted,
+                types: Types {
+                    schema: &schema,
+                    jute_module: "",
+                    module: vec![],
+                    options: match field.map_type {
+                        MapType::BTreeMap => &btree_map,
+                        MapType::HashMap => &hash_map,
+                    },
+                    unsigned_bytes: field.unsigned_bytes,
+                },
+            })
+            .collect();
+
+        let mut scope = Scope::new();
+        let record = scope
+            .new_impl(&self.name)
+            .impl_trait(format!("{}::Record", RUNTIME));
+        record.push_fn(generate_serialize(&fields));
+        record.push_fn(generate_deserialize(&fields));
+        scope
+            .new_impl(&self.name)
+            .impl_trait(format!("{}::JavaHashCode", RUNTIME))
+            .push_fn(generate_java_hash_code(&fields));
+
+        let comparable = class
+            .fields
+            .iter()
+            .all(|field| matches!(field.field_type, FieldType::Primitive(_)));
+        if comparable {
+            scope
+                .new_impl(&self.name)
+                .impl_trait(format!("{}::JavaComparable", RUNTIME))
+                .push_fn(generate_compare_to(&fields));
+        }
+
+        let write_signature = if fields
+            .iter()
+            .any(|field| contains_record(&field.field.field_type))
+        {
+            generate_nested_write_signature(&class.name, &fields)
+        } else {
+            scope.raw(&format!(
+                "impl {} {{\n    \
+                 /// The signature Java's Jute compiler gives this record.\n    \
+                 pub const SIGNATURE: &'static str = {:?};\n}}",
+                self.name,
+                signature::class_signature(&schema, &class)
+            ));
+            generate_write_signature()
+        };
+        scope
+            .new_impl(&self.name)
+            .impl_trait(format!("{}::JavaSignature", RUNTIME))
+            .push_fn(write_signature);
+        scope.to_string()
+    }
+}
+
+/// Emits `JavaSignature::write_signature` for a struct named `name` with
+/// nested records, which write their own signatures into it.
+fn generate_nested_write_signature(name: &str, fields: &[RecordField]) -> Function {
+    let mut function = Function::new("write_signature");
+    function
+        .arg("signature", "&mut ::std::string::String")
+        .arg("enclosing", "&mut ::std::vec::Vec<&'static str>")
+        .line(format!("signature.push_str(\"L{}\");", name));
+    let mut recursion = Block::new(&format!("if enclosing.contains(&{:?})", name));
+    recursion.line("return;");
+    function.push_block(recursion);
+    function.line(format!("enclosing.push({:?});", name));
+
+    let mut text = String::from("(");
+    for field in fields {
+        write_type_signature(&mut function, &mut text, field, &field.field.field_type);
+    }
+    text.push(')');
+    function.line(format!("signature.push_str({:?});", text));
+    function.line("enclosing.pop();");
+    function
+}
+
+/// Emits the lines writing the signature of `t`, collecting the signatures
+/// of types other than records in `text` until a record's is written.
+fn write_type_signature(
+    function: &mut Function,
+    text: &mut String,
+    field: &RecordField,
+    t: &FieldType,
+) {
+    match t {
+        FieldType::Primitive(PrimitiveFieldType::Custom(reference)) => {
+            if !text.is_empty() {
+                function.line(format!("signature.push_str({:?});", text));
+                text.clear();
+            }
+            function.line(format!(
+                "<{} as {}::JavaSignature>::write_signature(signature, enclosing);",
+                field.types.class_path(reference),
+                RUNTIME
+            ));
+        }
+        FieldType::Primitive(primitive) => text
+            .push(signature::primitive_signature(primitive).expect("the type not to be a record")),
+        FieldType::Vector(element_type) => {
+            text.push('[');
+            write_type_signature(function, text, field, element_type);
+            text.push(']');
+        }
+        FieldType::Map(key_type, value_type) => {
+            text.push('{');
+            write_type_signature(function, text, field, key_type);
+            write_type_signature(function, text, field, value_type);
+            text.push('}');
+        }
+    }
+}
+
+/// Returns whether a record appears anywhere in `t`.
+fn contains_record(t: &FieldType) -> bool {
+    match t {
+        FieldType::Primitive(primitive) => matches!(primitive, PrimitiveFieldType::Custom(_)),
+        FieldType::Vector(element_type) => contains_record(element_type),
+        FieldType::Map(key_type, value_type) => {
+            contains_record(key_type) || contains_record(value_type)
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use jute_rust_parser::{Class, FieldType, PrimitiveFieldType, Schema};
+
+/// Returns the signature Java's Jute compiler gives a class, such as
+/// `LTxnHeader(lilli)`: its unqualified name followed by the signatures of
+/// its fields, with nested records spelled out in full.
+pub(crate) fn class_signature(schema: &Schema, class: &Class) -> String {
+    let mut signature = String::new();
+    write_class(schema, class, &mut vec![], &mut signature);
+    signature
+}
+
+fn write_class<'a>(
+    schema: &'a Schema,
+    class: &'a Class,
+    enclosing: &mut Vec<&'a Class>,
+    signature: &mut String,
+) {
+    signature.push('L');
+    signature.push_str(&class.name);
+    // Java requires classes to be declared before they are used, so it has
+    // no recursive records; their signature stops at the recursion.
+    if enclosing.iter().any(|outer| std::ptr::eq(*outer, class)) {
+        return;
+    }
+
+    enclosing.push(class);
+    signature.push('(');
+    for field in &class.fields {
+        write_type(schema, &field.field_type, enclosing, signature);
+    }
+    signature.push(')');
+    enclosing.pop();
+}
+
+fn write_type<'a>(
+    schema: &'a Schema,
+    t: &FieldType,
+    enclosing: &mut Vec<&'a Class>,
+    signature: &mut String,
+) {
+    match t {
+        FieldType::Primitive(PrimitiveFieldType::Custom(reference)) => {
+            let target = reference.target.expect("schema to be resolved");
+            write_class(schema, schema.class(target), enclosing, signature);
+        }
+        FieldType::Primitive(primitive) => {
+            signature.push(primitive_signature(primitive).expect("the type not to be a record"))
+        }
+        FieldType::Vector(element_type) => {
+            signature.push('[');
+            write_type(schema, element_type, enclosing, signature);
+            signature.push(']');
+        }
+        FieldType::Map(key_type, value_type) => {
+            signature.push('{');
+            write_type(schema, key_type, enclosing, signature);
+            write_type(schema, value_type, enclosing, signature);
+            signature.push('}');
+        }
+    }
+}
+
+/// Returns the signature of a type that is not a record.
+pub(crate) fn primitive_signature(t: &PrimitiveFieldType) -> Option<char> {
+    match t {
+        PrimitiveFieldType::Boolean => Some('z'),
+        PrimitiveFieldType::Byte => Some('b'),
+        PrimitiveFieldType::Int => Some('i'),
+        PrimitiveFieldType::Long => Some('l'),
+        PrimitiveFieldType::Float => Some('f'),
+        PrimitiveFieldType::Double => Some('d'),
+        PrimitiveFieldType::String => Some('s'),
+        PrimitiveFieldType::Buffer => Some('B'),
+        PrimitiveFieldType::Custom(_) => None,
+    }
+}
@@ -0,0 +1,305 @@
+//! Generates Rust code for Jute schemas at compile time, without a build
+//! script or generated files in the source tree.
+//!
+//! The generated code refers to `jute_rust_runtime`, which the crate using
+//! these macros has to depend on. Depending on this crate under the name
+//! `jute` gives the macros the shorter `jute::include_schema!` path:
+//!
+//! ```toml
+//! [dependencies]
+//! jute = { package = "jute-rust-macros", path = "../jute-rust-macros" }
+//! jute-rust-runtime = { path = "../jute-rust-runtime" }
+//! ```
+extern crate jute_rust_codegen;
+extern crate jute_rust_parser;
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate quote;
+extern crate syn;
+
+mod record;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use jute_rust_codegen::{Diagnostic, Error, Runner};
+use proc_macro::{Delimiter, Literal, Span, TokenStream, TokenTree};
+
+/// Generates the modules of one or more Jute schema files, given as string
+/// literals relative to the directory of the crate's `Cargo.toml`:
+///
+/// ```ignore
+/// mod zookeeper {
+///     jute::include_schema!("src/zookeeper.jute");
+/// }
+/// ```
+///
+/// Files that refer to each other's classes have to be included together,
+/// or include each other. Problems with a schema become compile errors at
+/// its file name.
+#[proc_macro]
+pub fn include_schema(input: TokenStream) -> TokenStream {
+    let root = env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let mut runner = Runner::new();
+    let mut files = vec![];
+    for token in input {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => {}
+            TokenTree::Literal(literal) => match string_value(&literal) {
+                Some(file) => {
+                    let path = Path::new(&root).join(&file).to_string_lossy().into_owned();
+                    runner.add_file(path.clone());
+                    files.push((path, literal.span()));
+                }
+                None => return compile_error("expected a file name", literal.span()),
+            },
+            token => return compile_error("expected a file name", token.span()),
+        }
+    }
+    if files.is_empty() {
+        return compile_error("expected a file name", Span::call_site());
+    }
+
+    let (result, read) = runner.generate_tracked();
+    let code = match result {
+        Ok(code) => code,
+        Err(error) => {
+            return compile_errors(&error, |diagnostic| {
+                let span = files
+                    .iter()
+                    .find(|(path, _)| path == diagnostic.file())
+                    .map_or_else(Span::call_site, |(_, span)| *span);
+                (diagnostic.to_string(), span)
+            })
+        }
+    };
+
+    // Including the files makes the compiler rebuild the crate when they
+    // change, which it does not do for files a macro reads.
+    let mut tokens = TokenStream::new();
+    for path in read.iter().filter_map(|file| fs::canonicalize(file).ok()) {
+        tokens.extend(parse(&format!(
+            "const _: &[u8] = include_bytes!({:?});",
+            path
+        )));
+    }
+    tokens.extend(parse(&code));
+    tokens
+}
+
+/// Generates the modules of a Jute schema written inline:
+///
+/// ```ignore
+/// jute::schema! {
+///     module example {
+///         /// A point on the plane.
+///         class Point {
+///             int x;
+///             int y;
+///         }
+///     }
+/// }
+/// ```
+///
+/// Doc comments document the class or field that follows them; other
+/// comments are not visible to the macro. Problems with the schema become
+/// compile errors at the tokens they are about.
+#[proc_macro]
+pub fn schema(input: TokenStream) -> TokenStream {
+    let mut source = Source::default();
+    source.write(input);
+
+    let result = Runner::new()
+        .add_source("schema!".to_string(), source.text.clone())
+        .generate();
+    match result {
+        Ok(code) => parse(&code),
+        Err(error) => compile_errors(&error, |diagnostic| match diagnostic.span() {
+            Some(span) => {
+                // The position in the text the macro rebuilt from its input
+                // means nothing to the user; the span of the error does.
+                let message = diagnostic.to_string();
+                let prefix = format!("{}:{}: ", diagnostic.file(), span);
+                let message = message.strip_prefix(&prefix).unwrap_or(&message);
+                (message.to_string(), source.span_at(span.start))
+            }
+            None => (diagnostic.to_string(), Span::call_site()),
+        }),
+    }
+}
+
+/// Implements `jute_rust_runtime::Record` and the Java traits for a struct
+/// with named fields, wire compatible with the generated code for a Jute
+/// class with the same fields in the same order. Which traits those are is
+/// described at [`RecordStruct::generate`].
+///
+/// The Jute type of a field follows from the name of its Rust type: `bool`,
+/// `i8` or `u8` for `byte`, `i32`, `i64`, `f32`, `f64`, `String`, `Vec<u8>`
+/// for `buffer`, other `Vec`s, `BTreeMap` and `HashMap`. Fields of any other
+/// type have to be records themselves, implementing the same traits. The
+/// bytes of a field have to be all `i8` or all `u8`, and its maps all
+/// `BTreeMap`s or all `HashMap`s. Two attributes change that:
+///
+/// - `#[jute(type = "long")]` writes a field as the given Jute type. The
+///   field is cloned and converted with `From` to the Rust type generated
+///   for that Jute type, here `i64`, and converted back when read.
+/// - `#[jute(nullable)]` makes an `Option` of a `ustring`, `buffer` or
+///   `vector` write `None` as Java's null.
+///
+/// ```ignore
+/// #[derive(jute::JuteRecord)]
+/// struct TxnHeader {
+///     client_id: i64,
+///     cxid: i32,
+///     #[jute(type = "long")]
+///     zxid: Zxid,
+///     time: i64,
+///     r#type: i32,
+/// }
+/// ```
+///
+/// [`RecordStruct::generate`]: jute_rust_codegen::RecordStruct::generate
+#[proc_macro_derive(JuteRecord, attributes(jute))]
+pub fn derive_jute_record(input: TokenStream) -> TokenStream {
+    match syn::parse::<syn::DeriveInput>(input).and_then(record::derive) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Jute source text rebuilt from the tokens of a macro input, with the span
+/// of every token.
+#[derive(Default)]
+struct Source {
+    text: String,
+    /// The offset in `text` each token starts at, in order.
+    spans: Vec<(usize, Span)>,
+    /// Whether the text ends in an identifier or literal, which the next one
+    /// has to be separated from.
+    after_word: bool,
+}
+
+impl Source {
+    fn write(&mut self, tokens: TokenStream) {
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            match token {
+                TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                    let doc = match tokens.peek() {
+                        Some(TokenTree::Group(group)) => doc_text(group.stream()),
+                        _ => None,
+                    };
+                    match doc {
+                        Some(doc) => {
+                            tokens.next();
+                            self.write_comment(&doc, punct.span());
+                        }
+                        None => self.write_token("#", punct.span(), false),
+                    }
+                }
+                TokenTree::Punct(punct) => {
+                    self.write_token(&punct.as_char().to_string(), punct.span(), false)
+                }
+                TokenTree::Ident(ident) => self.write_token(&ident.to_string(), ident.span(), true),
+                TokenTree::Literal(literal) => {
+                    self.write_token(&literal.to_string(), literal.span(), true)
+                }
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::None => ("", ""),
+                    };
+                    self.write_token(open, group.span_open(), false);
+                    self.write(group.stream());
+                    self.write_token(close, group.span_close(), false);
+                }
+            }
+        }
+    }
+
+    fn write_token(&mut self, text: &str, span: Span, is_word: bool) {
+        if is_word && self.after_word {
+            self.text.push(' ');
+        }
+        self.spans.push((self.text.len(), span));
+        self.text.push_str(text);
+        self.after_word = is_word;
+    }
+
+    /// Writes a doc comment as Jute line comments on lines of their own,
+    /// which the parser attaches to the next declaration.
+    fn write_comment(&mut self, doc: &str, span: Span) {
+        self.spans.push((self.text.len(), span));
+        for line in doc.lines() {
+            self.text.push_str("\n//");
+            self.text.push_str(line);
+        }
+        self.text.push('\n');
+        self.after_word = false;
+    }
+
+    /// Returns the span of the token at `offset` in the text.
+    fn span_at(&self, offset: usize) -> Span {
+        self.spans
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= offset)
+            .map_or_else(Span::call_site, |(_, span)| *span)
+    }
+}
+
+/// Returns the text of the attribute `[doc = "..."]`, as which the compiler
+/// passes doc comments to macros.
+fn doc_text(attribute: TokenStream) -> Option<String> {
+    let tokens: Vec<TokenTree> = attribute.into_iter().collect();
+    match tokens.as_slice() {
+        [TokenTree::Ident(name), TokenTree::Punct(eq), TokenTree::Literal(literal)]
+            if name.to_string() == "doc" && eq.as_char() == '=' =>
+        {
+            string_value(literal)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the value of a Rust string literal, or `None` if `literal` is
+/// not one.
+fn string_value(literal: &Literal) -> Option<String> {
+    let tokens = TokenStream::from(TokenTree::Literal(literal.clone()));
+    syn::parse::<syn::LitStr>(tokens)
+        .ok()
+        .map(|literal| literal.value())
+}
+
+fn parse(code: &str) -> TokenStream {
+    code.parse().expect("generated code to be valid Rust")
+}
+
+/// Reports every diagnostic of `error` as a `compile_error!` at the span
+/// `locate` finds for it, together with its message.
+fn compile_errors<F>(error: &Error, locate: F) -> TokenStream
+where
+    F: Fn(&Diagnostic) -> (String, Span),
+{
+    error
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| {
+            let (message, span) = locate(diagnostic);
+            compile_error(&message, span)
+        })
+        .collect()
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    parse(&format!("compile_error!({:?});", message))
+        .into_iter()
+        .map(|mut token| {
+            token.set_span(span);
+            token
+        })
+        .collect()
+}
@@ -0,0 +1,443 @@
+use jute_rust_codegen::{MapType, RecordStruct, StructField};
+use jute_rust_parser::{FieldType, Parser, PrimitiveFieldType, Span, TypeReference};
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Data, DeriveInput, Fields, GenericArgument, LitStr, PathArguments, PathSegment, Type,
+};
+
+/// The settings of `#[jute(...)]` attributes on a field.
+#[derive(Default)]
+struct Attributes {
+    jute_type: Option<LitStr>,
+    nullable: bool,
+}
+
+/// The Rust types of the bytes and maps of a field, which the generated code
+/// has to agree on.
+#[derive(Default)]
+struct Representation {
+    unsigned_bytes: Option<bool>,
+    map_type: Option<MapType>,
+}
+
+/// Implements `Record` and the Java traits for a struct, writing its fields
+/// in order the way the generated code does.
+pub(crate) fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields: Vec<_> = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect(),
+            Fields::Unit => vec![],
+            Fields::Unnamed(_) => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "`JuteRecord` needs named fields, which Jute writes in order",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "`JuteRecord` can only be derived for structs",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "`JuteRecord` cannot be derived for generic structs",
+        ));
+    }
+
+    let mut record = RecordStruct {
+        name: input.ident.to_string(),
+        fields: vec![],
+    };
+    for field in fields {
+        let name = field.ident.as_ref().expect("named fields to have names");
+        let attributes = parse_attributes(&field.attrs)?;
+        let nullable = attributes.nullable;
+        let rust_type = if nullable {
+            option_argument(&field.ty).ok_or_else(|| {
+                syn::Error::new(
+                    field.ty.span(),
+                    "`#[jute(nullable)]` fields have to be `Option`s",
+                )
+            })?
+        } else {
+            &field.ty
+        };
+        let mut representation = Representation::default();
+        let field_type = match &attributes.jute_type {
+            Some(literal) => explicit_type(literal)?,
+            None => inferred_type(rust_type, &mut representation)?,
+        };
+        if nullable
+            && !matches!(
+                field_type,
+                FieldType::Primitive(PrimitiveFieldType::String)
+                    | FieldType::Primitive(PrimitiveFieldType::Buffer)
+                    | FieldType::Vector(_)
+            )
+        {
+            return Err(syn::Error::new(
+                field.span(),
+                "only `ustring`, `buffer` and `vector` fields can be nullable",
+            ));
+        }
+
+        record.fields.push(StructField {
+            name: name.to_string(),
+            field_type,
+            nullable,
+            unsigned_bytes: representation.unsigned_bytes.unwrap_or(false),
+            map_type: representation.map_type.unwrap_or(MapType::BTreeMap),
+            // Fields of an explicit type are converted to the Rust type the
+            // generated code uses for it, and back.
+            converted: attributes.jute_type.is_some(),
+        });
+    }
+
+    Ok(record
+        .generate()
+        .parse()
+        .expect("generated code to be valid Rust"))
+}
+
+fn parse_attributes(attributes: &[Attribute]) -> syn::Result<Attributes> {
+    let mut parsed = Attributes::default();
+    for attribute in attributes {
+        if !attribute.path().is_ident("jute") {
+            continue;
+        }
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("type") {
+                parsed.jute_type = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("nullable") {
+                parsed.nullable = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `type = \"...\"` or `nullable`"))
+            }
+        })?;
+    }
+    Ok(parsed)
+}
+
+/// Returns `T` if `t` is `Option<T>`.
+fn option_argument(t: &Type) -> Option<&Type> {
+    match t {
+        Type::Path(path) if path.qself.is_none() => {
+            let segment = path.path.segments.last()?;
+            match type_arguments(segment).as_slice() {
+                [argument] if segment.ident == "Option" => Some(argument),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn type_arguments(segment: &PathSegment) -> Vec<&Type> {
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) => arguments
+            .args
+            .iter()
+            .filter_map(|argument| match argument {
+                GenericArgument::Type(t) => Some(t),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Returns the Jute type of a field from its Rust type, looking at the type's
+/// name only. Types that are not Jute's are taken to be records.
+fn inferred_type(t: &Type, representation: &mut Representation) -> syn::Result<FieldType> {
+    let segment = match t {
+        Type::Paren(paren) => return inferred_type(&paren.elem, representation),
+        Type::Group(group) => return inferred_type(&group.elem, representation),
+        Type::Path(path) if path.qself.is_none() => match path.path.segments.last() {
+            Some(segment) => segment,
+            None => return Ok(record_type(t)),
+        },
+        _ => return Ok(record_type(t)),
+    };
+
+    let name = segment.ident.to_string();
+    let primitive = |primitive| Ok(FieldType::Primitive(primitive));
+    match (name.as_str(), type_arguments(segment).as_slice()) {
+        ("bool", []) => primitive(PrimitiveFieldType::Boolean),
+        ("i8", []) | ("u8", []) => {
+            let unsigned = name == "u8";
+            if *representation.unsigned_bytes.get_or_insert(unsigned) != unsigned {
+                return Err(syn::Error::new(
+                    t.span(),
+                    "the bytes of a field have to be all `i8` or all `u8`",
+                ));
+            }
+            primitive(PrimitiveFieldType::Byte)
+        }
+        ("i32", []) => primitive(PrimitiveFieldType::Int),
+        ("i64", []) => primitive(PrimitiveFieldType::Long),
+        ("f32", []) => primitive(PrimitiveFieldType::Float),
+        ("f64", []) => primitive(PrimitiveFieldType::Double),
+        ("String", []) => primitive(PrimitiveFieldType::String),
+        ("Vec", [element]) if is_u8(element) => primitive(PrimitiveFieldType::Buffer),
+        ("Vec", [element]) => Ok(FieldType::vector(inferred_type(element, representation)?)),
+        ("BTreeMap", [key, value]) | ("HashMap", [key, value]) => {
+            let map_type = if name == "BTreeMap" {
+                MapType::BTreeMap
+            } else {
+                MapType::HashMap
+            };
+            if *representation.map_type.get_or_insert(map_type) != map_type {
+                return Err(syn::Error::new(
+                    t.span(),
+                    "the maps of a field have to be all `BTreeMap`s or all `HashMap`s",
+                ));
+            }
+            Ok(FieldType::map(
+                inferred_type(key, representation)?,
+                inferred_type(value, representation)?,
+            ))
+        }
+        ("Option", [_]) => Err(syn::Error::new(
+            t.span(),
+            "`Option`s are only supported as `#[jute(nullable)]` fields",
+        )),
+        _ => Ok(record_type(t)),
+    }
+}
+
+fn is_u8(t: &Type) -> bool {
+    match t {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "u8" && type_arguments(segment).is_empty()),
+        _ => false,
+    }
+}
+
+/// Returns the type of a nested record, which names its Rust type.
+fn record_type(t: &Type) -> FieldType {
+    FieldType::Primitive(PrimitiveFieldType::Custom(TypeReference {
+        name: t.to_token_stream().to_string(),
+        span: Span::default(),
+        target: None,
+    }))
+}
+
+/// Returns the Jute type given by `#[jute(type = "...")]`, which may not
+/// name a class.
+fn explicit_type(literal: &LitStr) -> syn::Result<FieldType> {
+    let text = format!("module m {{ class C {{ {} f; }} }}", literal.value());
+    let field_type = Parser::from_string(&text)
+        .parse_all()
+        .ok()
+        .and_then(|schema| match schema.modules.as_slice() {
+            [module] => match module.classes.as_slice() {
+                [class] => match class.fields.as_slice() {
+                    [field] => Some(field.field_type.clone()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .ok_or_else(|| {
+            syn::Error::new(
+                literal.span(),
+                format!("`{}` is not a Jute type", literal.value()),
+            )
+        })?;
+    if names_class(&field_type) {
+        return Err(syn::Error::new(
+            literal.span(),
+            "`type` cannot name a class; fields that are records need no `type`",
+        ));
+    }
+    Ok(field_type)
+}
+
+fn names_class(t: &FieldType) -> bool {
+    match t {
+        FieldType::Primitive(primitive) => matches!(primitive, PrimitiveFieldType::Custom(_)),
+        FieldType::Vector(element_type) => names_class(element_type),
+        FieldType::Map(key_type, value_type) => names_class(key_type) || names_class(value_type),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn derive_error(input: DeriveInput) -> String {
+        derive(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_derive() {
+        let code = derive(syn::parse_quote! {
+            struct A {
+                r#type: u8,
+                #[jute(type = "vector<long>")]
+                ids: Ids,
+                #[jute(nullable)]
+                data: Option<Vec<u8>>,
+                #[jute(nullable, type = "ustring")]
+                name: Option<Name>,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(code.contains("archive . write_byte (self . r#type as i8 , \"type\") ?"));
+        assert!(code.contains("let r#type = archive . read_byte (\"type\") ? as u8 ;"));
+        assert!(code.contains(
+            "let self_ids : :: std :: vec :: Vec < i64 > = :: std :: convert :: From :: from"
+        ));
+        assert!(code.contains("archive . write_long (* e1 , \"e1\") ?"));
+        assert!(code.contains("archive . write_buffer (self . data . as_deref () , \"data\") ?"));
+        assert!(code.contains("let name = name . map (:: std :: convert :: From :: from) ;"));
+        assert!(code.contains("Self { r#type , ids , data , name , }"));
+        assert!(code.contains("impl :: jute_rust_runtime :: JavaHashCode for A"));
+        assert!(code.contains("pub const SIGNATURE : &'static str = \"LA(b[l]Bs)\" ;"));
+        assert!(code.contains("impl :: jute_rust_runtime :: JavaSignature for A"));
+        // Java cannot compare vectors.
+        assert!(!code.contains("JavaComparable"));
+
+        // Nested records compare and write their signatures themselves.
+        let code = derive(syn::parse_quote! {
+            struct B {
+                id: i64,
+                name: String,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(code.contains("impl :: jute_rust_runtime :: JavaComparable for B"));
+        assert!(code.contains("\"LB(ls)\""));
+        let code = derive(syn::parse_quote! {
+            struct C {
+                b: super::B,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(code.contains("archive . read_record ::< super :: B > (\"b\") ?"));
+        assert!(code.contains("JavaComparable :: compare_to (& self . b , & other . b)"));
+        assert!(code.contains(
+            "signature . push_str (\"LC\") ; \
+             if enclosing . contains (& \"C\") { return ; } \
+             enclosing . push (\"C\") ; \
+             signature . push_str (\"(\") ; \
+             < super :: B as :: jute_rust_runtime :: JavaSignature >:: write_signature (signature , enclosing) ; \
+             signature . push_str (\")\") ; \
+             enclosing . pop () ;"
+        ));
+        assert!(!code.contains("SIGNATURE"));
+
+        // The type may be local to a function, which `self::` cannot reach.
+        let code = derive(syn::parse_quote! {
+            struct D {
+                b: B,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(code.contains("archive . read_record ::< B > (\"b\") ?"));
+    }
+
+    #[test]
+    fn test_derive_errors() {
+        assert_eq!(
+            derive_error(syn::parse_quote!(
+                struct A(i32);
+            )),
+            "`JuteRecord` needs named fields, which Jute writes in order"
+        );
+        assert_eq!(
+            derive_error(syn::parse_quote!(
+                struct A<T> {
+                    t: T,
+                }
+            )),
+            "`JuteRecord` cannot be derived for generic structs"
+        );
+        assert_eq!(
+            derive_error(syn::parse_quote!(
+                struct A {
+                    name: Option<String>,
+                }
+            )),
+            "`Option`s are only supported as `#[jute(nullable)]` fields"
+        );
+        assert_eq!(
+            derive_error(syn::parse_quote!(
+                struct A {
+                    #[jute(nullable)]
+                    count: Option<i32>,
+                }
+            )),
+            "only `ustring`, `buffer` and `vector` fields can be nullable"
+        );
+        assert_eq!(
+            derive_error(syn::parse_quote!(
+                struct A {
+                    #[jute(nullable)]
+                    name: String,
+                }
+            )),
+            "`#[jute(nullable)]` fields have to be `Option`s"
+        );
+        assert_eq!(
+            derive_error(syn::parse_quote!(
+                struct A {
+                    #[jute(type = "vector<")]
+                    ids: Ids,
+                }
+            )),
+            "`vector<` is not a Jute type"
+        );
+        assert_eq!(
+            derive_error(syn::parse_quote!(
+                struct A {
+                    #[jute(type = "vector<a.B>")]
+                    ids: Ids,
+                }
+            )),
+            "`type` cannot name a class; fields that are records need no `type`"
+        );
+        assert_eq!(
+            derive_error(syn::parse_quote!(
+                struct A {
+                    bytes: BTreeMap<i8, Vec<u8>>,
+                    mixed: BTreeMap<i8, u8>,
+                }
+            )),
+            "the bytes of a field have to be all `i8` or all `u8`"
+        );
+        assert_eq!(
+            derive_error(syn::parse_quote!(
+                struct A {
+                    maps: BTreeMap<i32, HashMap<i32, i32>>,
+                }
+            )),
+            "the maps of a field have to be all `BTreeMap`s or all `HashMap`s"
+        );
+        assert_eq!(
+            derive_error(syn::parse_quote!(
+                struct A {
+                    #[jute(skip)]
+                    ids: Ids,
+                }
+            )),
+            "expected `type = \"...\"` or `nullable`"
+        );
+    }
+}
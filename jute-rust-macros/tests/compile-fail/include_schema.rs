@@ -0,0 +1,5 @@
+mod example {
+    jute_rust_macros::include_schema!("schemas/invalid.jute");
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+jute_rust_macros::schema! {
+    module example {
+        class Point {
+            int x;
+            Missing y;
+        }
+    }
+}
+
+fn main() {}
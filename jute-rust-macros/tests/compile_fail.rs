@@ -0,0 +1,19 @@
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn test_compile_errors() {
+    // `include_schema!` reads files relative to the crate it is used in,
+    // which here is the one trybuild builds the tests in.
+    let project = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("../tests/trybuild")
+        .join(env!("CARGO_PKG_NAME"));
+    fs::create_dir_all(project.join("schemas")).unwrap();
+    fs::copy(
+        "tests/compile-fail/invalid.jute",
+        project.join("schemas/invalid.jute"),
+    )
+    .unwrap();
+
+    trybuild::TestCases::new().compile_fail("tests/compile-fail/*.rs");
+}
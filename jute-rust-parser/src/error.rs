@@ -0,0 +1,119 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::Span;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ParseError {
+    /// A token other than the one the grammar requires.
+    UnexpectedToken {
+        expected: String,
+        found: String,
+        span: Span,
+    },
+    /// The input ended while the grammar still required a token.
+    UnexpectedEof { expected: String, span: Span },
+    /// A field type that does not name a Jute type.
+    InvalidType { found: String, span: Span },
+    /// A `/*` comment that is still open at the end of the input.
+    UnterminatedComment { span: Span },
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnexpectedEof { span, .. }
+            | ParseError::InvalidType { span, .. }
+            | ParseError::UnterminatedComment { span } => *span,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken {
+                expected,
+                found,
+                span,
+            } => write!(f, "{}: expected {}, found `{}`", span, expected, found),
+            ParseError::UnexpectedEof { expected, span } => {
+                write!(f, "{}: expected {}, found end of input", span, expected)
+            }
+            ParseError::InvalidType { found, span } => {
+                write!(f, "{}: `{}` is not a valid type", span, found)
+            }
+            ParseError::UnterminatedComment { span } => {
+                write!(f, "{}: unterminated block comment", span)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// A schema that parses but does not make sense as a whole.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ResolveError {
+    /// A type name that matches no class.
+    UnknownType {
+        module: usize,
+        name: String,
+        span: Span,
+    },
+    /// A second class of the same name in a module.
+    DuplicateClass {
+        module: usize,
+        name: String,
+        span: Span,
+    },
+    /// A second field of the same name in a class.
+    DuplicateField {
+        module: usize,
+        class: String,
+        name: String,
+        span: Span,
+    },
+}
+
+impl ResolveError {
+    /// Index into `Schema::modules` of the module the error is in.
+    pub fn module(&self) -> usize {
+        match self {
+            ResolveError::UnknownType { module, .. }
+            | ResolveError::DuplicateClass { module, .. }
+            | ResolveError::DuplicateField { module, .. } => *module,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            ResolveError::UnknownType { span, .. }
+            | ResolveError::DuplicateClass { span, .. }
+            | ResolveError::DuplicateField { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolveError::UnknownType { name, span, .. } => {
+                write!(f, "{}: unknown type `{}`", span, name)
+            }
+            ResolveError::DuplicateClass { name, span, .. } => {
+                write!(f, "{}: class `{}` is defined more than once", span, name)
+            }
+            ResolveError::DuplicateField {
+                class, name, span, ..
+            } => write!(
+                f,
+                "{}: field `{}` is defined more than once in class `{}`",
+                span, name, class
+            ),
+        }
+    }
+}
+
+impl Error for ResolveError {}
@@ -1,39 +1,93 @@
-use crate::Tokenizer;
+use std::fmt;
+
+use crate::{ParseError, Span, Tokenizer};
 
 #[derive(PartialEq, Debug)]
 pub enum LexedToken<'a> {
     Identifier(&'a str),
+    /// A `"` quoted string, quotes included.
+    QuotedString(&'a str),
+    IncludeKeyword,
     ModuleKeyword,
     ClassKeyword,
     LeftBracket,
     RightBracket,
+    LeftAngleBracket,
+    RightAngleBracket,
+    Comma,
     Semicolon,
+    /// A comment on a line of its own, or one that starts the input.
+    Comment(&'a str),
+    /// A comment on the same line as the token before it.
+    TrailingComment(&'a str),
 }
 
 pub struct Lexer<'a> {
     tokenizer: Tokenizer<'a>,
+    previous_line: Option<usize>,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(tokenizer: Tokenizer<'a>) -> Lexer<'a> {
-        Lexer { tokenizer }
+        Lexer {
+            tokenizer,
+            previous_line: None,
+        }
+    }
+}
+
+impl<'a> fmt::Display for LexedToken<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexedToken::Identifier(text)
+            | LexedToken::QuotedString(text)
+            | LexedToken::Comment(text)
+            | LexedToken::TrailingComment(text) => f.write_str(text),
+            LexedToken::IncludeKeyword => f.write_str("include"),
+            LexedToken::ModuleKeyword => f.write_str("module"),
+            LexedToken::ClassKeyword => f.write_str("class"),
+            LexedToken::LeftBracket => f.write_str("{"),
+            LexedToken::RightBracket => f.write_str("}"),
+            LexedToken::LeftAngleBracket => f.write_str("<"),
+            LexedToken::RightAngleBracket => f.write_str(">"),
+            LexedToken::Comma => f.write_str(","),
+            LexedToken::Semicolon => f.write_str(";"),
+        }
     }
 }
 
 impl<'a> Iterator for Lexer<'a> {
-    type Item = LexedToken<'a>;
+    type Item = Result<(LexedToken<'a>, Span), ParseError>;
 
-    fn next(&mut self) -> Option<LexedToken<'a>> {
+    fn next(&mut self) -> Option<Result<(LexedToken<'a>, Span), ParseError>> {
         if let Some(token) = self.tokenizer.next() {
-            let token = match token.text {
+            let token = match token {
+                Ok(token) => token,
+                Err(error) => return Some(Err(error)),
+            };
+            let span = token.span;
+            let previous_line = self.previous_line.replace(span.line);
+            let lexed = match token.text {
+                comment if comment.starts_with("//") || comment.starts_with("/*") => {
+                    if previous_line == Some(span.line) {
+                        LexedToken::TrailingComment(comment)
+                    } else {
+                        LexedToken::Comment(comment)
+                    }
+                }
+                string if string.starts_with('"') => LexedToken::QuotedString(string),
+                "include" => LexedToken::IncludeKeyword,
                 "module" => LexedToken::ModuleKeyword,
                 "class" => LexedToken::ClassKeyword,
                 "{" => LexedToken::LeftBracket,
                 "}" => LexedToken::RightBracket,
+                "<" => LexedToken::LeftAngleBracket,
+                ">" => LexedToken::RightAngleBracket,
+                "," => LexedToken::Comma,
                 ";" => LexedToken::Semicolon,
                 _ => LexedToken::Identifier(token.text),
             };
-            Some(token)
+            Some(Ok((lexed, span)))
         } else {
             None
         }
@@ -47,35 +101,33 @@ mod tests {
     fn test_parse(text: &str, output: Vec<LexedToken>) {
         let tokenizer = Tokenizer::new(text);
 
-        let mut lexer = Lexer {
-            tokenizer: tokenizer,
-        };
+        let mut lexer = Lexer::new(tokenizer);
 
         for expected in output {
-            if let Some(token) = lexer.next() {
+            if let Some(Ok((token, _))) = lexer.next() {
                 assert_eq!(token, expected);
             } else {
-                assert!(false)
+                panic!("expected another token")
             }
         }
 
         if let Some(_token) = lexer.next() {
-            assert!(false)
+            panic!("expected end of input")
         }
     }
 
     #[test]
     fn test_empty_string() {
-        test_parse(&"", vec![])
+        test_parse("", vec![])
     }
 
     #[test]
     fn test_single_field() {
         test_parse(
-            &"long cxid;",
+            "long cxid;",
             vec![
-                LexedToken::Identifier(&"long"),
-                LexedToken::Identifier(&"cxid"),
+                LexedToken::Identifier("long"),
+                LexedToken::Identifier("cxid"),
                 LexedToken::Semicolon,
             ],
         )
@@ -89,19 +141,61 @@ mod tests {
             ustring id;
         }";
         test_parse(
-            &text,
+            text,
             vec![
                 LexedToken::ClassKeyword,
-                LexedToken::Identifier(&"Id"),
+                LexedToken::Identifier("Id"),
                 LexedToken::LeftBracket,
-                LexedToken::Identifier(&"ustring"),
-                LexedToken::Identifier(&"scheme"),
+                LexedToken::Identifier("ustring"),
+                LexedToken::Identifier("scheme"),
                 LexedToken::Semicolon,
-                LexedToken::Identifier(&"ustring"),
-                LexedToken::Identifier(&"id"),
+                LexedToken::Identifier("ustring"),
+                LexedToken::Identifier("id"),
                 LexedToken::Semicolon,
                 LexedToken::RightBracket,
             ],
         )
     }
+
+    #[test]
+    fn test_comments() {
+        let text = "
+        // information shared with the client
+        class Stat { /* fields */
+            long czxid; // created zxid
+        }";
+        test_parse(
+            text,
+            vec![
+                LexedToken::Comment("// information shared with the client"),
+                LexedToken::ClassKeyword,
+                LexedToken::Identifier("Stat"),
+                LexedToken::LeftBracket,
+                LexedToken::TrailingComment("/* fields */"),
+                LexedToken::Identifier("long"),
+                LexedToken::Identifier("czxid"),
+                LexedToken::Semicolon,
+                LexedToken::TrailingComment("// created zxid"),
+                LexedToken::RightBracket,
+            ],
+        )
+    }
+
+    #[test]
+    fn test_generics() {
+        test_parse(
+            "map< ustring,vector <int>>",
+            vec![
+                LexedToken::Identifier("map"),
+                LexedToken::LeftAngleBracket,
+                LexedToken::Identifier("ustring"),
+                LexedToken::Comma,
+                LexedToken::Identifier("vector"),
+                LexedToken::LeftAngleBracket,
+                LexedToken::Identifier("int"),
+                LexedToken::RightAngleBracket,
+                LexedToken::RightAngleBracket,
+            ],
+        )
+    }
 }
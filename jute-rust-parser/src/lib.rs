@@ -1,7 +1,12 @@
+mod error;
 mod lexer;
 mod parser;
+mod resolve;
+mod span;
 mod tokenizer;
 
+pub use error::*;
 use lexer::*;
 pub use parser::*;
+pub use span::*;
 use tokenizer::*;
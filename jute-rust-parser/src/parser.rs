@@ -1,6 +1,7 @@
-use crate::Tokenizer;
 use crate::{LexedToken, Lexer};
+use crate::{ParseError, Span, Tokenizer};
 
+use std::iter::Peekable;
 use std::result::Result;
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -13,216 +14,515 @@ pub enum PrimitiveFieldType {
     Int,
     Long,
     String,
-    Custom(String),
+    Custom(TypeReference),
+}
+
+/// A use of a class name as a type, as written in the schema.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct TypeReference {
+    pub name: String,
+    pub span: Span,
+    /// The class the name refers to, once `Schema::resolve` has run.
+    pub target: Option<ClassId>,
+}
+
+/// Identifies a class by its position in `Schema::modules`.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub struct ClassId {
+    pub module: usize,
+    pub class: usize,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub enum FieldType {
     Primitive(PrimitiveFieldType),
-    Map(PrimitiveFieldType, PrimitiveFieldType),
-    Vector(PrimitiveFieldType),
+    Map(Box<FieldType>, Box<FieldType>),
+    Vector(Box<FieldType>),
+}
+
+impl FieldType {
+    pub fn vector(element_type: FieldType) -> FieldType {
+        FieldType::Vector(Box::new(element_type))
+    }
+
+    pub fn map(key_type: FieldType, value_type: FieldType) -> FieldType {
+        FieldType::Map(Box::new(key_type), Box::new(value_type))
+    }
+}
+
+/// Comments the schema author attached to a class or field.
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub struct Comments {
+    /// Comments on the lines right before the declaration.
+    pub leading: Vec<String>,
+    /// A comment on the same line, after the declaration.
+    pub trailing: Option<String>,
+}
+
+impl Comments {
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty() && self.trailing.is_none()
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct Field {
     pub name: String,
     pub field_type: FieldType,
+    pub comments: Comments,
+    /// From the field type to the closing `;`.
+    pub span: Span,
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct Class {
     pub name: String,
     pub fields: Vec<Field>,
+    pub comments: Comments,
+    /// From the `class` keyword to the closing `}`.
+    pub span: Span,
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct Module {
     pub name: String,
     pub classes: Vec<Class>,
+    /// From the `module` keyword to the closing `}`.
+    pub span: Span,
+}
+
+/// An `include "file.jute"` directive, which makes the modules of another
+/// file part of the schema.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Include {
+    /// The file name without the quotes.
+    pub path: String,
+    /// From the `include` keyword to the file name.
+    pub span: Span,
+}
+
+/// All modules of one or more schema files.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Schema {
+    pub modules: Vec<Module>,
+    /// The files the schema includes, which are not parsed along with it.
+    pub includes: Vec<Include>,
+}
+
+impl Schema {
+    pub fn class(&self, id: ClassId) -> &Class {
+        &self.modules[id.module].classes[id.class]
+    }
 }
 
 pub struct Parser<'a> {
-    lexer: Lexer<'a>,
+    text: &'a str,
+    lexer: Peekable<Lexer<'a>>,
+    /// Comments seen since the last declaration.
+    comments: Vec<String>,
+    /// The `include` directives parsed so far.
+    includes: Vec<Include>,
 }
 
 impl<'a> Parser<'a> {
     pub fn from_string(text: &'a str) -> Parser<'a> {
         let tokenizer = Tokenizer::new(text);
-        let lexer = Lexer::new(tokenizer);
-        Parser { lexer }
+        let lexer = Lexer::new(tokenizer).peekable();
+        Parser {
+            text,
+            lexer,
+            comments: vec![],
+            includes: vec![],
+        }
     }
-}
 
-impl<'a> Parser<'a> {
-    pub fn next(&mut self) -> Result<Module, String> {
-        let mut module = Module {
-            name: "".to_string(),
-            classes: vec![],
-        };
+    /// Parses the remaining modules, reporting every syntax error found
+    /// rather than just the first.
+    pub fn parse_all(mut self) -> Result<Schema, Vec<ParseError>> {
+        let mut schema = Schema::default();
+        let mut errors = vec![];
+        for result in &mut self {
+            match result {
+                Ok(module) => schema.modules.push(module),
+                Err(e) => errors.push(e),
+            }
+        }
+        schema.includes = self.includes;
 
-        if let Err(e) = self.expect_lexed_token(LexedToken::ModuleKeyword) {
-            return Err(e.to_string());
+        if errors.is_empty() {
+            Ok(schema)
+        } else {
+            Err(errors)
         }
+    }
+}
+
+/// Yields one module at a time and ends at the end of the input. After a
+/// syntax error the parser skips ahead to the next `module` keyword.
+/// `include` directives are collected along the way, and returned by
+/// `parse_all`.
+impl<'a> Iterator for Parser<'a> {
+    type Item = Result<Module, ParseError>;
 
-        if let Some(token) = self.lexer.next() {
-            if let LexedToken::Identifier(name) = token {
-                module.name = name.to_string();
-            } else {
-                return Err("something else".to_string());
+    fn next(&mut self) -> Option<Result<Module, ParseError>> {
+        loop {
+            if self.at_end() {
+                return None;
             }
-        } else {
-            return Err("eof".to_string());
+
+            let result = match self.lexer.peek() {
+                Some(Ok((LexedToken::IncludeKeyword, _))) => match self.parse_include() {
+                    Ok(include) => {
+                        self.includes.push(include);
+                        continue;
+                    }
+                    Err(error) => Err(error),
+                },
+                _ => self.parse_module(),
+            };
+            if result.is_err() {
+                self.recover();
+            }
+            return Some(result);
         }
+    }
+}
 
-        if let Err(e) = self.expect_lexed_token(LexedToken::LeftBracket) {
-            return Err(e.to_string());
+impl<'a> Parser<'a> {
+    fn parse_include(&mut self) -> Result<Include, ParseError> {
+        let start = self.expect_lexed_token(LexedToken::IncludeKeyword)?;
+        self.comments.clear();
+        match self.next_token()? {
+            Some((LexedToken::QuotedString(text), end))
+                if text.len() > 1 && text.ends_with('"') =>
+            {
+                Ok(Include {
+                    path: text[1..text.len() - 1].to_string(),
+                    span: start.to(end),
+                })
+            }
+            other => Err(self.unexpected("quoted file name", other)),
         }
+    }
+
+    fn parse_module(&mut self) -> Result<Module, ParseError> {
+        let start = self.expect_lexed_token(LexedToken::ModuleKeyword)?;
+
+        let mut module = Module {
+            name: self.expect_identifier("module name", true)?,
+            classes: vec![],
+            span: start,
+        };
+
+        self.expect_lexed_token(LexedToken::LeftBracket)?;
+        self.comments.clear();
 
         loop {
-            if let Some(token) = self.lexer.next() {
-                if let LexedToken::ClassKeyword = token {
-                    let parsed_class = self.parse_class();
-                    if let Ok(class) = parsed_class {
-                        module.classes.push(class);
-                    } else {
-                        return Err(parsed_class.err().expect("logic error"));
-                    }
-                } else if let LexedToken::RightBracket = token {
+            match self.next_token()? {
+                Some((LexedToken::ClassKeyword, span)) => {
+                    module.classes.push(self.parse_class(span)?)
+                }
+                Some((LexedToken::RightBracket, end)) => {
+                    self.comments.clear();
+                    module.span = start.to(end);
                     return Ok(module);
-                } else {
-                    return Err("super_weird".to_string());
                 }
-            } else {
-                return Err("ended too quickly".to_string());
+                other => return Err(self.unexpected("`class` or `}`", other)),
             }
         }
     }
 
-    fn parse_class(&mut self) -> Result<Class, String> {
+    fn parse_class(&mut self, start: Span) -> Result<Class, ParseError> {
+        let leading = std::mem::take(&mut self.comments);
         let mut class = Class {
-            name: String::new(),
+            name: self.expect_identifier("class name", false)?,
             fields: vec![],
+            comments: Comments {
+                leading,
+                trailing: None,
+            },
+            span: start,
         };
 
-        if let Some(name_token) = self.lexer.next() {
-            if let LexedToken::Identifier(name) = name_token {
-                class.name = name.to_string();
-            } else {
-                return Err("bla".to_string());
-            }
-        } else {
-            return Err("bla".to_string());
-        }
-
-        if let Err(e) = self.expect_lexed_token(LexedToken::LeftBracket) {
-            return Err(e.to_string());
-        }
+        self.expect_lexed_token(LexedToken::LeftBracket)?;
+        class.comments.trailing = self.trailing_comment();
+        self.comments.clear();
 
         loop {
-            if let Some(token) = self.lexer.next() {
-                if let LexedToken::Identifier(property_type) = token {
-                    if let Ok(field) = self.parse_field(property_type) {
-                        class.fields.push(field);
-                    } else {
-                        return Err("bla".to_string());
-                    }
-                } else if let LexedToken::RightBracket = token {
+            match self.next_token()? {
+                Some((LexedToken::Identifier(type_name), span)) => {
+                    class.fields.push(self.parse_field(type_name, span)?)
+                }
+                Some((LexedToken::RightBracket, end)) => {
+                    self.comments.clear();
+                    class.span = start.to(end);
                     return Ok(class);
-                } else {
-                    return Err("super weird stuff".to_string());
                 }
-            } else {
-                return Err("bla".to_string());
+                other => return Err(self.unexpected("field type or `}`", other)),
             }
         }
     }
 
-    fn parse_field(&mut self, field_type: &str) -> Result<Field, String> {
+    fn parse_field(&mut self, type_name: &'a str, type_span: Span) -> Result<Field, ParseError> {
+        let leading = std::mem::take(&mut self.comments);
         let mut field = Field {
-            field_type: self.parse_field_type(field_type),
-            name: String::new(),
+            field_type: self.parse_named_type(type_name, type_span)?,
+            name: self.expect_identifier("field name", false)?,
+            comments: Comments {
+                leading,
+                trailing: None,
+            },
+            span: type_span,
         };
 
-        if let Some(token) = self.lexer.next() {
-            if let LexedToken::Identifier(name) = token {
-                field.name = name.to_string();
-            } else {
-                return Err("bla".to_string());
-            }
-        } else {
-            return Err("bla".to_string());
-        }
+        let end = self.expect_lexed_token(LexedToken::Semicolon)?;
+        field.span = type_span.to(end);
 
-        if let Err(e) = self.expect_lexed_token(LexedToken::Semicolon) {
-            return Err(e.to_string());
-        }
+        field.comments.trailing = self.trailing_comment();
+        self.comments.clear();
 
         Ok(field)
     }
 
-    fn parse_field_type(&self, field_type: &str) -> FieldType {
-        match field_type {
-            "boolean" => FieldType::Primitive(PrimitiveFieldType::Boolean),
-            "buffer" => FieldType::Primitive(PrimitiveFieldType::Buffer),
-            "byte" => FieldType::Primitive(PrimitiveFieldType::Byte),
-            "double" => FieldType::Primitive(PrimitiveFieldType::Double),
-            "float" => FieldType::Primitive(PrimitiveFieldType::Float),
-            "int" => FieldType::Primitive(PrimitiveFieldType::Int),
-            "long" => FieldType::Primitive(PrimitiveFieldType::Long),
-            "ustring" => FieldType::Primitive(PrimitiveFieldType::String),
-            other => {
-                if other.starts_with("vector") {
-                    let inner_type = &other[7..(other.len() - 1)];
-                    if let FieldType::Primitive(primitive_type) = self.parse_field_type(inner_type)
-                    {
-                        return FieldType::Vector(primitive_type);
-                    }
+    /// Parses a type, following the grammar
+    ///
+    /// ```text
+    /// type := "boolean" | "buffer" | "byte" | "double" | "float" | "int"
+    ///       | "long" | "ustring" | name
+    ///       | "vector" "<" type ">"
+    ///       | "map" "<" type "," type ">"
+    /// ```
+    fn parse_type(&mut self) -> Result<FieldType, ParseError> {
+        match self.next_token()? {
+            Some((LexedToken::Identifier(name), span)) => self.parse_named_type(name, span),
+            other => Err(self.unexpected("type", other)),
+        }
+    }
+
+    /// Parses the rest of a type that starts with the identifier `name`.
+    fn parse_named_type(&mut self, name: &'a str, span: Span) -> Result<FieldType, ParseError> {
+        let primitive_type = match name {
+            "boolean" => PrimitiveFieldType::Boolean,
+            "buffer" => PrimitiveFieldType::Buffer,
+            "byte" => PrimitiveFieldType::Byte,
+            "double" => PrimitiveFieldType::Double,
+            "float" => PrimitiveFieldType::Float,
+            "int" => PrimitiveFieldType::Int,
+            "long" => PrimitiveFieldType::Long,
+            "ustring" => PrimitiveFieldType::String,
+            "vector" => {
+                self.expect_lexed_token(LexedToken::LeftAngleBracket)?;
+                let element_type = self.parse_type()?;
+                self.expect_lexed_token(LexedToken::RightAngleBracket)?;
+                return Ok(FieldType::vector(element_type));
+            }
+            "map" => {
+                self.expect_lexed_token(LexedToken::LeftAngleBracket)?;
+                let key_type = self.parse_type()?;
+                self.expect_lexed_token(LexedToken::Comma)?;
+                let value_type = self.parse_type()?;
+                self.expect_lexed_token(LexedToken::RightAngleBracket)?;
+                return Ok(FieldType::map(key_type, value_type));
+            }
+            name if is_identifier(name, true) => PrimitiveFieldType::Custom(TypeReference {
+                name: name.to_string(),
+                span,
+                target: None,
+            }),
+            _ => {
+                return Err(ParseError::InvalidType {
+                    found: name.to_string(),
+                    span,
+                })
+            }
+        };
+
+        Ok(FieldType::Primitive(primitive_type))
+    }
+
+    /// Returns the next token that is not a comment. Comments on lines of
+    /// their own are kept for the declaration that follows them.
+    fn next_token(&mut self) -> Result<Option<(LexedToken<'a>, Span)>, ParseError> {
+        loop {
+            match self.lexer.next().transpose()? {
+                Some((LexedToken::Comment(text), _)) => self.comments.push(comment_text(text)),
+                Some((LexedToken::TrailingComment(_), _)) => {}
+                token => return Ok(token),
+            }
+        }
+    }
+
+    /// Checks whether only comments are left, consuming them.
+    fn at_end(&mut self) -> bool {
+        loop {
+            match self.lexer.peek() {
+                Some(Ok((LexedToken::Comment(text), _))) => {
+                    let text = comment_text(text);
+                    self.comments.push(text);
                 }
-                FieldType::Primitive(PrimitiveFieldType::Custom(field_type.to_string()))
+                Some(Ok((LexedToken::TrailingComment(_), _))) => {}
+                Some(_) => return false,
+                None => return true,
             }
+            self.lexer.next();
         }
     }
 
-    fn expect_lexed_token(&mut self, expected_token: LexedToken) -> Result<LexedToken, &str> {
-        if let Some(token) = self.lexer.next() {
-            if token == expected_token {
-                Ok(token)
-            } else {
-                Err("got wrong token")
+    /// Skips the rest of a module with a syntax error.
+    fn recover(&mut self) {
+        while let Some(token) = self.lexer.peek() {
+            if let Ok((LexedToken::ModuleKeyword, _)) = token {
+                break;
             }
+            self.lexer.next();
+        }
+        self.comments.clear();
+    }
+
+    /// Consumes the comment following the previous token on the same line.
+    fn trailing_comment(&mut self) -> Option<String> {
+        if let Some(Ok((LexedToken::TrailingComment(text), _))) = self.lexer.peek() {
+            let text = comment_text(text);
+            self.lexer.next();
+            Some(text)
         } else {
-            Err("got wrong token")
+            None
+        }
+    }
+
+    /// Builds the error for finding `found` where `expected` was required.
+    fn unexpected(&self, expected: &str, found: Option<(LexedToken, Span)>) -> ParseError {
+        match found {
+            Some((token, span)) => ParseError::UnexpectedToken {
+                expected: expected.to_string(),
+                found: token.to_string(),
+                span,
+            },
+            None => ParseError::UnexpectedEof {
+                expected: expected.to_string(),
+                span: Span::end_of(self.text),
+            },
+        }
+    }
+
+    fn expect_lexed_token(&mut self, expected_token: LexedToken) -> Result<Span, ParseError> {
+        match self.next_token()? {
+            Some((token, span)) if token == expected_token => Ok(span),
+            other => Err(self.unexpected(&format!("`{}`", expected_token), other)),
         }
     }
+
+    /// Consumes the name of a declaration. Module names may contain dots.
+    fn expect_identifier(&mut self, expected: &str, dotted: bool) -> Result<String, ParseError> {
+        match self.next_token()? {
+            Some((LexedToken::Identifier(name), _)) if is_identifier(name, dotted) => {
+                Ok(name.to_string())
+            }
+            other => Err(self.unexpected(expected, other)),
+        }
+    }
+}
+
+/// Checks that `text` is an identifier, or a dot-separated path of them.
+fn is_identifier(text: &str, dotted: bool) -> bool {
+    let is_part = |part: &str| {
+        let mut chars = part.chars();
+        matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_')
+    };
+
+    if dotted {
+        text.split('.').all(is_part)
+    } else {
+        is_part(text)
+    }
+}
+
+/// Strips the comment delimiters and the decoration of block comments.
+fn comment_text(comment: &str) -> String {
+    if let Some(text) = comment.strip_prefix("//") {
+        return text.trim().to_string();
+    }
+
+    let text = comment.trim_start_matches("/*");
+    let text = text.strip_suffix("*/").unwrap_or(text);
+    let lines: Vec<&str> = text
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        })
+        .collect();
+
+    let first = lines.iter().position(|line| !line.is_empty());
+    let last = lines.iter().rposition(|line| !line.is_empty());
+    match (first, last) {
+        (Some(first), Some(last)) => lines[first..=last].join("\n"),
+        _ => String::new(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn reference(name: &str) -> TypeReference {
+        TypeReference {
+            name: name.to_string(),
+            span: Span::default(),
+            target: None,
+        }
+    }
+
+    fn clear_type_span(field_type: &mut FieldType) {
+        match field_type {
+            FieldType::Primitive(PrimitiveFieldType::Custom(reference)) => {
+                reference.span = Span::default()
+            }
+            FieldType::Primitive(_) => {}
+            FieldType::Vector(element_type) => clear_type_span(element_type),
+            FieldType::Map(key_type, value_type) => {
+                clear_type_span(key_type);
+                clear_type_span(value_type);
+            }
+        }
+    }
+
+    /// Resets all spans, for comparing against modules written out by hand.
+    fn without_spans(mut module: Module) -> Module {
+        module.span = Span::default();
+        for class in &mut module.classes {
+            class.span = Span::default();
+            for field in &mut class.fields {
+                field.span = Span::default();
+                clear_type_span(&mut field.field_type);
+            }
+        }
+        module
+    }
+
     fn test_parse(text: &str, output: Vec<Module>) {
         let mut parser = Parser::from_string(text);
 
         for expected in output {
             match parser.next() {
-                Ok(module) => {
-                    assert_eq!(module, expected);
-                }
-                Err(e) => {
-                    println!("{}", e);
-                    assert!(false);
+                Some(Ok(module)) => {
+                    assert_eq!(without_spans(module), expected);
                 }
+                Some(Err(e)) => panic!("{}", e),
+                None => panic!("expected another module"),
             };
         }
 
-        if let Ok(_token) = parser.next() {
-            assert!(false)
+        if parser.next().is_some() {
+            panic!("expected end of input")
         }
     }
 
     #[test]
     fn test_empty_string() {
-        test_parse(&"", vec![])
+        test_parse("", vec![])
     }
 
     #[test]
@@ -275,24 +575,36 @@ module org.apache.zookeeper.proto {
                                 Field {
                                     name: "clientId".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Long),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "cxid".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Int),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "zxid".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Long),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "time".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Long),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "type".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Int),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                             ],
+                            comments: Comments::default(),
+                            span: Span::default(),
                         },
                         Class {
                             name: "CreateTxn".to_string(),
@@ -300,28 +612,43 @@ module org.apache.zookeeper.proto {
                                 Field {
                                     name: "path".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::String),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "data".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Buffer),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "acl".to_string(),
-                                    field_type: FieldType::Vector(PrimitiveFieldType::Custom(
-                                        "org.apache.zookeeper.data.ACL".to_string(),
+                                    field_type: FieldType::vector(FieldType::Primitive(
+                                        PrimitiveFieldType::Custom(reference(
+                                            "org.apache.zookeeper.data.ACL",
+                                        )),
                                     )),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "ephemeral".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Boolean),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "parentCVersion".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Int),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                             ],
+                            comments: Comments::default(),
+                            span: Span::default(),
                         },
                     ],
+                    span: Span::default(),
                 },
                 Module {
                     name: "org.apache.zookeeper.proto".to_string(),
@@ -332,24 +659,36 @@ module org.apache.zookeeper.proto {
                                 Field {
                                     name: "protocolVersion".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Int),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "lastZxidSeen".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Long),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "timeOut".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Int),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "sessionId".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Long),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "passwd".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Buffer),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                             ],
+                            comments: Comments::default(),
+                            span: Span::default(),
                         },
                         Class {
                             name: "SetWatches".to_string(),
@@ -357,31 +696,52 @@ module org.apache.zookeeper.proto {
                                 Field {
                                     name: "relativeZxid".to_string(),
                                     field_type: FieldType::Primitive(PrimitiveFieldType::Long),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "dataWatches".to_string(),
-                                    field_type: FieldType::Vector(PrimitiveFieldType::String),
+                                    field_type: FieldType::vector(FieldType::Primitive(
+                                        PrimitiveFieldType::String,
+                                    )),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "existWatches".to_string(),
-                                    field_type: FieldType::Vector(PrimitiveFieldType::String),
+                                    field_type: FieldType::vector(FieldType::Primitive(
+                                        PrimitiveFieldType::String,
+                                    )),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                                 Field {
                                     name: "childWatches".to_string(),
-                                    field_type: FieldType::Vector(PrimitiveFieldType::String),
+                                    field_type: FieldType::vector(FieldType::Primitive(
+                                        PrimitiveFieldType::String,
+                                    )),
+                                    comments: Comments::default(),
+                                    span: Span::default(),
                                 },
                             ],
+                            comments: Comments::default(),
+                            span: Span::default(),
                         },
                         Class {
                             name: "SetDataResponse".to_string(),
                             fields: vec![Field {
                                 name: "stat".to_string(),
                                 field_type: FieldType::Primitive(PrimitiveFieldType::Custom(
-                                    "org.apache.zookeeper.data.Stat".to_string(),
+                                    reference("org.apache.zookeeper.data.Stat"),
                                 )),
+                                comments: Comments::default(),
+                                span: Span::default(),
                             }],
+                            comments: Comments::default(),
+                            span: Span::default(),
                         },
                     ],
+                    span: Span::default(),
                 },
             ],
         )
@@ -407,18 +767,461 @@ module org.apache.zookeeper.server.persistence {
                         Field {
                             name: "magic".to_string(),
                             field_type: FieldType::Primitive(PrimitiveFieldType::Int),
+                            comments: Comments::default(),
+                            span: Span::default(),
                         },
                         Field {
                             name: "version".to_string(),
                             field_type: FieldType::Primitive(PrimitiveFieldType::Int),
+                            comments: Comments::default(),
+                            span: Span::default(),
                         },
                         Field {
                             name: "dbid".to_string(),
                             field_type: FieldType::Primitive(PrimitiveFieldType::Long),
+                            comments: Comments::default(),
+                            span: Span::default(),
                         },
                     ],
+                    comments: Comments::default(),
+                    span: Span::default(),
                 }],
+                span: Span::default(),
+            }],
+        )
+    }
+
+    #[test]
+    fn test_upstream_zookeeper_schema() {
+        let mut parser = Parser::from_string(include_str!("testdata/zookeeper.jute"));
+
+        let data = parser.next().unwrap().unwrap();
+        assert_eq!(data.name, "org.apache.zookeeper.data");
+        assert_eq!(data.classes.len(), 5);
+        assert_eq!(data.classes[2].name, "Stat");
+        assert_eq!(data.classes[2].fields.len(), 11);
+
+        let names: Vec<String> = (0..4)
+            .map(|_| parser.next().unwrap().unwrap().name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "org.apache.zookeeper.proto",
+                "org.apache.zookeeper.server.quorum",
+                "org.apache.zookeeper.server.persistence",
+                "org.apache.zookeeper.txn",
+            ]
+        );
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn test_comments() {
+        test_parse(
+            "
+/**
+ * License header.
+ */
+module org.apache.zookeeper.data {
+    // information shared with the client
+    /* spans
+     * two lines */
+    class Stat { // trailing class comment
+        long czxid;      // created zxid
+        // leading field comment
+        long mzxid;
+        // dangling comment
+    }
+    class Id {
+        ustring scheme;
+    } // not attached to anything
+}
+",
+            vec![Module {
+                name: "org.apache.zookeeper.data".to_string(),
+                classes: vec![
+                    Class {
+                        name: "Stat".to_string(),
+                        fields: vec![
+                            Field {
+                                name: "czxid".to_string(),
+                                field_type: FieldType::Primitive(PrimitiveFieldType::Long),
+                                comments: Comments {
+                                    leading: vec![],
+                                    trailing: Some("created zxid".to_string()),
+                                },
+                                span: Span::default(),
+                            },
+                            Field {
+                                name: "mzxid".to_string(),
+                                field_type: FieldType::Primitive(PrimitiveFieldType::Long),
+                                comments: Comments {
+                                    leading: vec!["leading field comment".to_string()],
+                                    trailing: None,
+                                },
+                                span: Span::default(),
+                            },
+                        ],
+                        comments: Comments {
+                            leading: vec![
+                                "information shared with the client".to_string(),
+                                "spans\ntwo lines".to_string(),
+                            ],
+                            trailing: Some("trailing class comment".to_string()),
+                        },
+                        span: Span::default(),
+                    },
+                    Class {
+                        name: "Id".to_string(),
+                        fields: vec![Field {
+                            name: "scheme".to_string(),
+                            field_type: FieldType::Primitive(PrimitiveFieldType::String),
+                            comments: Comments::default(),
+                            span: Span::default(),
+                        }],
+                        comments: Comments::default(),
+                        span: Span::default(),
+                    },
+                ],
+                span: Span::default(),
             }],
         )
     }
+
+    fn parse_error(text: &str) -> ParseError {
+        match Parser::from_string(text).next() {
+            Some(Ok(module)) => panic!("expected an error, got {:?}", module),
+            Some(Err(e)) => e,
+            None => panic!("expected an error, got end of input"),
+        }
+    }
+
+    #[test]
+    fn test_unexpected_token() {
+        let error = parse_error("module a {\n    class B {\n        long x\n    }\n}");
+        assert_eq!(
+            error,
+            ParseError::UnexpectedToken {
+                expected: "`;`".to_string(),
+                found: "}".to_string(),
+                span: Span {
+                    start: 44,
+                    end: 45,
+                    line: 4,
+                    column: 5,
+                },
+            }
+        );
+        assert_eq!(error.to_string(), "4:5: expected `;`, found `}`");
+
+        assert_eq!(
+            parse_error("class A {}").to_string(),
+            "1:1: expected `module`, found `class`"
+        );
+        assert_eq!(
+            parse_error("module a { class 1B {} }").to_string(),
+            "1:18: expected class name, found `1B`"
+        );
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        let error = parse_error("module a {\n    class B {\n");
+        assert_eq!(
+            error,
+            ParseError::UnexpectedEof {
+                expected: "field type or `}`".to_string(),
+                span: Span {
+                    start: 25,
+                    end: 25,
+                    line: 3,
+                    column: 1,
+                },
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "3:1: expected field type or `}`, found end of input"
+        );
+    }
+
+    #[test]
+    fn test_invalid_type() {
+        let error = parse_error("module a {\n    class B {\n        1.x y;");
+        assert_eq!(
+            error,
+            ParseError::InvalidType {
+                found: "1.x".to_string(),
+                span: Span {
+                    start: 33,
+                    end: 36,
+                    line: 3,
+                    column: 9,
+                },
+            }
+        );
+        assert_eq!(error.to_string(), "3:9: `1.x` is not a valid type");
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        let errors = Parser::from_string(
+            "module a { class B { int x; } } /* oops\nmodule c { class D { int y; } }",
+        )
+        .parse_all()
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![ParseError::UnterminatedComment {
+                span: Span {
+                    start: 32,
+                    end: 71,
+                    line: 1,
+                    column: 33,
+                },
+            }]
+        );
+        assert_eq!(errors[0].to_string(), "1:33: unterminated block comment");
+
+        let error = parse_error("module a {\n    class B { /* int x; }\n}");
+        assert_eq!(error.to_string(), "2:15: unterminated block comment");
+    }
+
+    #[test]
+    fn test_malformed_generics() {
+        let error = parse_error("module a {\n    class B {\n        vector<map<int>> x;");
+        assert_eq!(
+            error,
+            ParseError::UnexpectedToken {
+                expected: "`,`".to_string(),
+                found: ">".to_string(),
+                span: Span {
+                    start: 47,
+                    end: 48,
+                    line: 3,
+                    column: 23,
+                },
+            }
+        );
+
+        assert_eq!(
+            parse_error("module a { class B { vector<long x; } }").to_string(),
+            "1:34: expected `>`, found `x`"
+        );
+        assert_eq!(
+            parse_error("module a { class B { vector<long>> x; } }").to_string(),
+            "1:34: expected field name, found `>`"
+        );
+        assert_eq!(
+            parse_error("module a { class B { vector long x; } }").to_string(),
+            "1:29: expected `<`, found `long`"
+        );
+        assert_eq!(
+            parse_error("module a { class B { map<,int> x; } }").to_string(),
+            "1:26: expected type, found `,`"
+        );
+    }
+
+    fn parse_type(text: &str) -> FieldType {
+        let text = format!("module a {{ class B {{ {} x; }} }}", text);
+        let module = Parser::from_string(&text).next().unwrap().unwrap();
+        module.classes[0].fields[0].field_type.clone()
+    }
+
+    #[test]
+    fn test_maps() {
+        let expected = FieldType::map(
+            FieldType::Primitive(PrimitiveFieldType::String),
+            FieldType::Primitive(PrimitiveFieldType::Int),
+        );
+        assert_eq!(parse_type("map<ustring,int>"), expected);
+        assert_eq!(parse_type("map<ustring, int>"), expected);
+        assert_eq!(parse_type("map< ustring ,  int >"), expected);
+        assert_eq!(parse_type("map <ustring\n    ,int>"), expected);
+
+        assert_eq!(
+            parse_type("map<long, a.C>"),
+            FieldType::map(
+                FieldType::Primitive(PrimitiveFieldType::Long),
+                FieldType::Primitive(PrimitiveFieldType::Custom(TypeReference {
+                    name: "a.C".to_string(),
+                    span: Span {
+                        start: 31,
+                        end: 34,
+                        line: 1,
+                        column: 32,
+                    },
+                    target: None,
+                })),
+            )
+        );
+    }
+
+    #[test]
+    fn test_nested_types() {
+        let long = || FieldType::Primitive(PrimitiveFieldType::Long);
+        assert_eq!(
+            parse_type("vector<vector<long>>"),
+            FieldType::vector(FieldType::vector(long()))
+        );
+        assert_eq!(
+            parse_type("vector < vector<long > >"),
+            FieldType::vector(FieldType::vector(long()))
+        );
+        assert_eq!(
+            parse_type("map<long, map<long,vector< vector<long> > >>"),
+            FieldType::map(
+                long(),
+                FieldType::map(long(), FieldType::vector(FieldType::vector(long())))
+            )
+        );
+
+        let mut acls = parse_type("map<ustring, vector<org.apache.zookeeper.data.ACL>>");
+        clear_type_span(&mut acls);
+        assert_eq!(
+            acls,
+            FieldType::map(
+                FieldType::Primitive(PrimitiveFieldType::String),
+                FieldType::vector(FieldType::Primitive(PrimitiveFieldType::Custom(reference(
+                    "org.apache.zookeeper.data.ACL"
+                )))),
+            )
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let text = "module a {\n    class B {\n        vector<a.C> cs;\n    }\n}\n";
+        let module = Parser::from_string(text).next().unwrap().unwrap();
+        assert_eq!(
+            module.span,
+            Span {
+                start: 0,
+                end: 56,
+                line: 1,
+                column: 1,
+            }
+        );
+
+        let class = &module.classes[0];
+        assert_eq!(
+            class.span,
+            Span {
+                start: 15,
+                end: 54,
+                line: 2,
+                column: 5,
+            }
+        );
+        assert_eq!(
+            &text[class.span.start..class.span.end],
+            "class B {\n        vector<a.C> cs;\n    }"
+        );
+
+        let field = &class.fields[0];
+        assert_eq!(&text[field.span.start..field.span.end], "vector<a.C> cs;");
+        assert_eq!((field.span.line, field.span.column), (3, 9));
+
+        assert_eq!(
+            field.field_type,
+            FieldType::vector(FieldType::Primitive(PrimitiveFieldType::Custom(
+                TypeReference {
+                    name: "a.C".to_string(),
+                    span: Span {
+                        start: 40,
+                        end: 43,
+                        line: 3,
+                        column: 16,
+                    },
+                    target: None,
+                }
+            )))
+        );
+    }
+
+    #[test]
+    fn test_end_of_input() {
+        assert!(Parser::from_string("").next().is_none());
+        assert!(
+            Parser::from_string("  // only a comment\n/* and another */")
+                .next()
+                .is_none()
+        );
+
+        let mut parser = Parser::from_string("module a {}\n// trailing comment\n");
+        assert!(parser.next().unwrap().is_ok());
+        assert!(parser.next().is_none());
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn test_parse_all() {
+        let schema = Parser::from_string("module a { class B {} } module c {}")
+            .parse_all()
+            .unwrap();
+        let names: Vec<&str> = schema.modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "c"]);
+
+        let errors = Parser::from_string(
+            "module a { class B { long; } }
+module b { class C { int x; } }
+module c { class D { x } }",
+        )
+        .parse_all()
+        .unwrap_err();
+        let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "1:26: expected field name, found `;`",
+                "3:24: expected field name, found `}`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_includes() {
+        let text = "// The ZooKeeper types.
+include \"zookeeper.jute\"
+module a { class B { org.apache.zookeeper.data.Id id; } }
+include \"dir/other file.jute\"";
+        let schema = Parser::from_string(text).parse_all().unwrap();
+        assert_eq!(schema.modules.len(), 1);
+        assert_eq!(
+            schema.includes,
+            vec![
+                Include {
+                    path: "zookeeper.jute".to_string(),
+                    span: Span {
+                        start: 24,
+                        end: 48,
+                        line: 2,
+                        column: 1
+                    },
+                },
+                Include {
+                    path: "dir/other file.jute".to_string(),
+                    span: Span {
+                        start: 107,
+                        end: 136,
+                        line: 4,
+                        column: 1
+                    },
+                },
+            ]
+        );
+
+        let errors = Parser::from_string("include zookeeper.jute\nmodule a {}\ninclude \"open")
+            .parse_all()
+            .unwrap_err();
+        let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "1:9: expected quoted file name, found `zookeeper.jute`",
+                "3:9: expected quoted file name, found `\"open`",
+            ]
+        );
+    }
 }
@@ -0,0 +1,175 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use crate::{ClassId, FieldType, PrimitiveFieldType, ResolveError, Schema, TypeReference};
+
+impl Schema {
+    /// Links every type reference to the class it names and checks that
+    /// names are unique. A name containing dots is looked up as
+    /// `module.Class`, any other name in the module of the field.
+    pub fn resolve(&mut self) -> Result<(), Vec<ResolveError>> {
+        let mut errors = vec![];
+        let mut classes = HashMap::new();
+
+        for (module_index, module) in self.modules.iter().enumerate() {
+            for (class_index, class) in module.classes.iter().enumerate() {
+                match classes.entry(format!("{}.{}", module.name, class.name)) {
+                    Entry::Occupied(_) => errors.push(ResolveError::DuplicateClass {
+                        module: module_index,
+                        name: class.name.clone(),
+                        span: class.span,
+                    }),
+                    Entry::Vacant(entry) => {
+                        entry.insert(ClassId {
+                            module: module_index,
+                            class: class_index,
+                        });
+                    }
+                }
+
+                let mut fields = HashSet::new();
+                for field in &class.fields {
+                    if !fields.insert(&field.name) {
+                        errors.push(ResolveError::DuplicateField {
+                            module: module_index,
+                            class: class.name.clone(),
+                            name: field.name.clone(),
+                            span: field.span,
+                        });
+                    }
+                }
+            }
+        }
+
+        for (module_index, module) in self.modules.iter_mut().enumerate() {
+            let module_name = &module.name;
+            for class in &mut module.classes {
+                for field in &mut class.fields {
+                    visit_references(&mut field.field_type, &mut |reference| {
+                        let name = if reference.name.contains('.') {
+                            reference.name.clone()
+                        } else {
+                            format!("{}.{}", module_name, reference.name)
+                        };
+
+                        reference.target = classes.get(&name).copied();
+                        if reference.target.is_none() {
+                            errors.push(ResolveError::UnknownType {
+                                module: module_index,
+                                name: reference.name.clone(),
+                                span: reference.span,
+                            });
+                        }
+                    });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn visit_references(field_type: &mut FieldType, visit: &mut dyn FnMut(&mut TypeReference)) {
+    match field_type {
+        FieldType::Primitive(PrimitiveFieldType::Custom(reference)) => visit(reference),
+        FieldType::Primitive(_) => {}
+        FieldType::Vector(element_type) => visit_references(element_type, visit),
+        FieldType::Map(key_type, value_type) => {
+            visit_references(key_type, visit);
+            visit_references(value_type, visit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Class, Parser};
+
+    fn parse(texts: &[&str]) -> Schema {
+        let mut schema = Schema::default();
+        for text in texts {
+            schema
+                .modules
+                .extend(Parser::from_string(text).parse_all().unwrap().modules);
+        }
+        schema
+    }
+
+    fn target(schema: &Schema, module: usize, class: usize, field: usize) -> &Class {
+        let mut field_type = &schema.modules[module].classes[class].fields[field].field_type;
+        while let FieldType::Vector(element_type) = field_type {
+            field_type = element_type;
+        }
+        match field_type {
+            FieldType::Primitive(PrimitiveFieldType::Custom(reference)) => {
+                schema.class(reference.target.expect("reference to be resolved"))
+            }
+            other => panic!("unexpected field type {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_relative_and_qualified_references() {
+        let mut schema = parse(&[
+            "module a.data {
+                class Id { ustring id; }
+                class ACL { int perms; Id id; }
+            }",
+            "module a.proto {
+                class Create { vector<a.data.ACL> acl; a.data.Id owner; }
+            }",
+        ]);
+        schema.resolve().unwrap();
+
+        assert_eq!(target(&schema, 0, 1, 1).name, "Id");
+        assert_eq!(target(&schema, 1, 0, 0).name, "ACL");
+        assert_eq!(target(&schema, 1, 0, 1).name, "Id");
+    }
+
+    #[test]
+    fn test_modules_split_across_files() {
+        let mut schema = parse(&[
+            "module a { class B { C c; } }",
+            "module a { class C { int x; } }",
+        ]);
+        schema.resolve().unwrap();
+
+        assert_eq!(target(&schema, 0, 0, 0).name, "C");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut schema = parse(&[
+            "module a {
+    class B { Missing m; int x; long x; }
+    class B { b.B other; }
+}",
+            "module b {
+    class C { map<ustring, vector<a.Missing>> m; B b; }
+}",
+        ]);
+        let errors: Vec<String> = schema
+            .resolve()
+            .unwrap_err()
+            .iter()
+            .map(|e| format!("{} {}", e.module(), e))
+            .collect();
+
+        assert_eq!(
+            errors,
+            vec![
+                "0 2:33: field `x` is defined more than once in class `B`",
+                "0 3:5: class `B` is defined more than once",
+                "0 2:15: unknown type `Missing`",
+                "0 3:15: unknown type `b.B`",
+                "1 2:35: unknown type `a.Missing`",
+                "1 2:50: unknown type `B`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,41 @@
+use std::fmt;
+
+/// A region of the source text.
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy)]
+pub struct Span {
+    /// Byte offset of the first character.
+    pub start: usize,
+    /// Byte offset just past the last character.
+    pub end: usize,
+    /// Line of the first character, counting from 1.
+    pub line: usize,
+    /// Column of the first character in characters, counting from 1.
+    pub column: usize,
+}
+
+impl Span {
+    /// Returns the empty span right after the last character of `text`.
+    pub fn end_of(text: &str) -> Span {
+        let line_start = text.rfind('\n').map_or(0, |i| i + 1);
+        Span {
+            start: text.len(),
+            end: text.len(),
+            line: text.matches('\n').count() + 1,
+            column: text[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// Returns the span from the start of `self` to the end of `end`.
+    pub fn to(self, end: Span) -> Span {
+        Span {
+            end: end.end,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
@@ -1,84 +1,152 @@
 use std::iter::Peekable;
 use std::str::CharIndices;
 
+use crate::{ParseError, Span};
+
 pub struct Token<'a> {
     pub text: &'a str,
+    pub span: Span,
 }
 
 pub struct Tokenizer<'a> {
     text: &'a str,
     iter: Peekable<CharIndices<'a>>,
+    line: usize,
+    column: usize,
 }
 
 impl<'a> Tokenizer<'a> {
     pub fn new(text: &'a str) -> Tokenizer<'a> {
         Tokenizer {
-            text: text,
+            text,
             iter: text.char_indices().peekable(),
+            line: 1,
+            column: 1,
         }
     }
 }
 
-impl<'a> Iterator for Tokenizer<'a> {
-    type Item = Token<'a>;
-
-    fn next(&mut self) -> Option<Token<'a>> {
-        let mut initial_begin_set: bool = false;
-        let mut begin: usize = 0;
-        let mut end: usize = 0;
-
-        loop {
-            let peek = self.iter.peek();
-
-            if peek.is_none() {
-                if begin < end {
-                    return Some(Token {
-                        text: &self.text[begin..end],
-                    });
-                } else {
-                    return None;
+impl<'a> Tokenizer<'a> {
+    fn peek_char(&mut self) -> Option<char> {
+        self.iter.peek().map(|&(_, c)| c)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let (_, c) = self.iter.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn at_comment(&self) -> bool {
+        let mut lookahead = self.iter.clone();
+        matches!(
+            (lookahead.next(), lookahead.next()),
+            (Some((_, '/')), Some((_, '/'))) | (Some((_, '/')), Some((_, '*')))
+        )
+    }
+
+    /// Consumes a `//` line or `/* */` block comment, leaving the newline
+    /// that ends a line comment in place. Returns `false` for a block
+    /// comment that is still open at the end of the input.
+    fn skip_comment(&mut self) -> bool {
+        self.bump();
+        if self.bump() == Some('/') {
+            while let Some(c) = self.peek_char() {
+                if c == '\n' {
+                    break;
                 }
+                self.bump();
             }
+        } else {
+            while let Some(c) = self.bump() {
+                if c == '*' && self.peek_char() == Some('/') {
+                    self.bump();
+                    return true;
+                }
+            }
+            return false;
+        }
+        true
+    }
 
-            if let Some(y) = peek {
-                let (i, c) = y;
+    /// Consumes a `"` quoted string, which ends at the end of the line if
+    /// it is not closed.
+    fn skip_string(&mut self) {
+        self.bump();
+        while let Some(c) = self.peek_char() {
+            if c == '\n' {
+                break;
+            }
+            self.bump();
+            if c == '"' {
+                break;
+            }
+        }
+    }
 
-                if c.is_whitespace() {
-                    if begin < end {
-                        return Some(Token {
-                            text: &self.text[begin..end],
-                        });
-                    }
-                    begin = *i + 1;
-                    end = *i + 1;
-                } else if c == &';' {
-                    if begin < end {
-                        return Some(Token {
-                            text: &self.text[begin..end],
-                        });
-                    }
+    fn position(&mut self) -> usize {
+        match self.iter.peek() {
+            Some(&(i, _)) => i,
+            None => self.text.len(),
+        }
+    }
+}
 
-                    begin = *i;
-                    end = *i + 1;
-                } else if c == &'>' {
-                    self.iter.next();
-
-                    return Some(Token {
-                        text: &self.text[begin..end + 1],
-                    });
-                } else {
-                    if !initial_begin_set {
-                        initial_begin_set = true;
-                        begin = *i;
-                    }
-                    end = *i + 1;
-                }
+/// Characters that form a token of their own.
+fn is_punctuation(c: char) -> bool {
+    matches!(c, ';' | '{' | '}' | '<' | '>' | ',')
+}
 
-                self.iter.next();
+/// Yields the tokens of the input, or an error for a block comment that is
+/// never closed, after which it ends.
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Result<Token<'a>, ParseError>> {
+        while self.peek_char()?.is_whitespace() {
+            self.bump();
+        }
 
-                continue;
+        let begin = self.position();
+        let (line, column) = (self.line, self.column);
+
+        let mut closed = true;
+        if self.at_comment() {
+            closed = self.skip_comment();
+        } else if self.peek_char() == Some('"') {
+            self.skip_string();
+        } else {
+            let first = self.bump()?;
+            if !is_punctuation(first) {
+                while let Some(c) = self.peek_char() {
+                    if c.is_whitespace() || is_punctuation(c) || c == '"' || self.at_comment() {
+                        break;
+                    }
+
+                    self.bump();
+                }
             }
         }
+
+        let end = self.position();
+        let span = Span {
+            start: begin,
+            end,
+            line,
+            column,
+        };
+        if !closed {
+            return Some(Err(ParseError::UnterminatedComment { span }));
+        }
+        Some(Ok(Token {
+            text: &self.text[begin..end],
+            span,
+        }))
     }
 }
 
@@ -91,25 +159,25 @@ mod tests {
 
         for expected in output {
             if let Some(token) = tokenizer.next() {
-                assert_eq!(token.text, expected);
+                assert_eq!(token.unwrap().text, expected);
             } else {
-                assert!(false)
+                panic!("expected another token")
             }
         }
 
         if let Some(_token) = tokenizer.next() {
-            assert!(false);
+            panic!("expected end of input")
         }
     }
 
     #[test]
     fn test_empty_string() {
-        test_parse(&"", vec![])
+        test_parse("", vec![])
     }
 
     #[test]
     fn test_single_field() {
-        test_parse(&"long cxid;", vec![&"long", &"cxid", &";"])
+        test_parse("long cxid;", vec!["long", "cxid", ";"])
     }
 
     #[test]
@@ -120,9 +188,9 @@ mod tests {
             ustring id;
         }";
         test_parse(
-            &text,
+            text,
             vec![
-                &"class", &"Id", &"{", &"ustring", &"scheme", &";", &"ustring", &"id", &";", &"}",
+                "class", "Id", "{", "ustring", "scheme", ";", "ustring", "id", ";", "}",
             ],
         )
     }
@@ -130,6 +198,142 @@ mod tests {
     #[test]
     fn test_generic_without_spaces() {
         let text = "vector<ustring>dataWatches;";
-        test_parse(&text, vec![&"vector<ustring>", &"dataWatches", &";"])
+        test_parse(
+            text,
+            vec!["vector", "<", "ustring", ">", "dataWatches", ";"],
+        )
+    }
+
+    #[test]
+    fn test_generic_with_spaces() {
+        test_parse(
+            "map< ustring ,vector <int> >counts;",
+            vec![
+                "map", "<", "ustring", ",", "vector", "<", "int", ">", ">", "counts", ";",
+            ],
+        )
+    }
+
+    #[test]
+    fn test_quoted_strings() {
+        test_parse(
+            "include \"a b/c.jute\"module\"open\nx",
+            vec!["include", "\"a b/c.jute\"", "module", "\"open", "x"],
+        )
+    }
+
+    #[test]
+    fn test_semicolon_followed_by_bracket() {
+        test_parse(
+            "class A{int x;}",
+            vec!["class", "A", "{", "int", "x", ";", "}"],
+        )
+    }
+
+    #[test]
+    fn test_line_comments() {
+        let text = "
+        // information shared with the client
+        long czxid;      // created zxid
+        int dataLength;  //length of the data in the node
+        long pzxid;// last modified children";
+        test_parse(
+            text,
+            vec![
+                "// information shared with the client",
+                "long",
+                "czxid",
+                ";",
+                "// created zxid",
+                "int",
+                "dataLength",
+                ";",
+                "//length of the data in the node",
+                "long",
+                "pzxid",
+                ";",
+                "// last modified children",
+            ],
+        )
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let text = "/**
+ * Licensed to the Apache Software Foundation (ASF) under one
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ */
+module /* inline */ a.b {/**/}";
+        test_parse(
+            text,
+            vec![
+                "/**
+ * Licensed to the Apache Software Foundation (ASF) under one
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ */",
+                "module",
+                "/* inline */",
+                "a.b",
+                "{",
+                "/**/",
+                "}",
+            ],
+        )
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let mut tokenizer = Tokenizer::new("long x; /* never\nclosed ;");
+        let texts: Vec<&str> = tokenizer
+            .by_ref()
+            .take(3)
+            .map(|token| token.unwrap().text)
+            .collect();
+        assert_eq!(texts, vec!["long", "x", ";"]);
+        assert_eq!(
+            tokenizer.next().map(|token| token.err()),
+            Some(Some(ParseError::UnterminatedComment {
+                span: Span {
+                    start: 8,
+                    end: 25,
+                    line: 1,
+                    column: 9,
+                },
+            }))
+        );
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn test_comment_directly_after_identifier() {
+        test_parse("x// y\nz/* w */", vec!["x", "// y", "z", "/* w */"])
+    }
+
+    #[test]
+    fn test_spans() {
+        let spans: Vec<(usize, usize, usize, usize)> =
+            Tokenizer::new("a /* b\n c */ d\n\n e // f\ngö;")
+                .map(|token| {
+                    let span = token.unwrap().span;
+                    (span.start, span.end, span.line, span.column)
+                })
+                .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (0, 1, 1, 1),
+                (2, 12, 1, 3),
+                (13, 14, 2, 7),
+                (17, 18, 4, 2),
+                (19, 23, 4, 4),
+                (24, 27, 5, 1),
+                (27, 28, 5, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slash_inside_identifier() {
+        test_parse("a/b", vec!["a/b"])
     }
 }
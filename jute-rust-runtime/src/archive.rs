@@ -0,0 +1,89 @@
+use std::io;
+
+use crate::Record;
+
+/// Destination of a serialized record, mirroring Java's `org.apache.jute.OutputArchive`.
+///
+/// Every method takes the tag Java passes along: the field name, or the name
+/// of the loop variable for vector and map elements. Binary archives ignore
+/// it, textual formats use it to label values. `None` stands in for Java's
+/// `null` where the Java archive accepts one.
+pub trait OutputArchive {
+    /// Writes Java's signed `byte`.
+    fn write_byte(&mut self, value: i8, tag: &str) -> io::Result<()>;
+
+    fn write_bool(&mut self, value: bool, tag: &str) -> io::Result<()>;
+
+    fn write_int(&mut self, value: i32, tag: &str) -> io::Result<()>;
+
+    fn write_long(&mut self, value: i64, tag: &str) -> io::Result<()>;
+
+    fn write_float(&mut self, value: f32, tag: &str) -> io::Result<()>;
+
+    fn write_double(&mut self, value: f64, tag: &str) -> io::Result<()>;
+
+    fn write_string(&mut self, value: Option<&str>, tag: &str) -> io::Result<()>;
+
+    fn write_buffer(&mut self, value: Option<&[u8]>, tag: &str) -> io::Result<()>;
+
+    fn write_record<R: Record>(&mut self, record: &R, tag: &str) -> io::Result<()>
+    where
+        Self: Sized,
+    {
+        record.serialize(self, tag)
+    }
+
+    fn start_record(&mut self, tag: &str) -> io::Result<()>;
+
+    fn end_record(&mut self, tag: &str) -> io::Result<()>;
+
+    fn start_vector(&mut self, len: Option<usize>, tag: &str) -> io::Result<()>;
+
+    fn end_vector(&mut self, tag: &str) -> io::Result<()>;
+
+    fn start_map(&mut self, len: usize, tag: &str) -> io::Result<()>;
+
+    fn end_map(&mut self, tag: &str) -> io::Result<()>;
+}
+
+/// Source of a serialized record, mirroring Java's `org.apache.jute.InputArchive`.
+///
+/// `start_vector` returns the number of elements to read, or `None` where
+/// Java's archive would return a `null` index.
+pub trait InputArchive {
+    /// Reads Java's signed `byte`.
+    fn read_byte(&mut self, tag: &str) -> io::Result<i8>;
+
+    fn read_bool(&mut self, tag: &str) -> io::Result<bool>;
+
+    fn read_int(&mut self, tag: &str) -> io::Result<i32>;
+
+    fn read_long(&mut self, tag: &str) -> io::Result<i64>;
+
+    fn read_float(&mut self, tag: &str) -> io::Result<f32>;
+
+    fn read_double(&mut self, tag: &str) -> io::Result<f64>;
+
+    fn read_string(&mut self, tag: &str) -> io::Result<Option<String>>;
+
+    fn read_buffer(&mut self, tag: &str) -> io::Result<Option<Vec<u8>>>;
+
+    fn read_record<R: Record>(&mut self, tag: &str) -> io::Result<R>
+    where
+        Self: Sized,
+    {
+        R::deserialize(self, tag)
+    }
+
+    fn start_record(&mut self, tag: &str) -> io::Result<()>;
+
+    fn end_record(&mut self, tag: &str) -> io::Result<()>;
+
+    fn start_vector(&mut self, tag: &str) -> io::Result<Option<usize>>;
+
+    fn end_vector(&mut self, tag: &str) -> io::Result<()>;
+
+    fn start_map(&mut self, tag: &str) -> io::Result<usize>;
+
+    fn end_map(&mut self, tag: &str) -> io::Result<()>;
+}
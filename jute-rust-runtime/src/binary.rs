@@ -0,0 +1,314 @@
+use std::io::{self, Read, Write};
+
+use crate::{InputArchive, OutputArchive, Record};
+
+/// Largest string or buffer `BinaryInputArchive` accepts by default, the
+/// default of Java's `jute.maxbuffer` property.
+pub const DEFAULT_MAX_BUFFER: usize = 0xfffff;
+
+/// Writes records in the layout of Java's `BinaryOutputArchive`: big-endian
+/// numbers, one-byte booleans and `i32` length prefixes, with `-1` for null.
+pub struct BinaryOutputArchive<W> {
+    writer: W,
+}
+
+impl<W: Write> BinaryOutputArchive<W> {
+    pub fn new(writer: W) -> BinaryOutputArchive<W> {
+        BinaryOutputArchive { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_length(&mut self, len: Option<usize>) -> io::Result<()> {
+        match len {
+            Some(len) if len > i32::MAX as usize => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("length {} does not fit into an int", len),
+            )),
+            Some(len) => self.writer.write_all(&(len as i32).to_be_bytes()),
+            None => self.writer.write_all(&(-1i32).to_be_bytes()),
+        }
+    }
+}
+
+impl<W: Write> OutputArchive for BinaryOutputArchive<W> {
+    fn write_byte(&mut self, value: i8, _tag: &str) -> io::Result<()> {
+        self.writer.write_all(&value.to_be_bytes())
+    }
+
+    fn write_bool(&mut self, value: bool, _tag: &str) -> io::Result<()> {
+        self.writer.write_all(&[value as u8])
+    }
+
+    fn write_int(&mut self, value: i32, _tag: &str) -> io::Result<()> {
+        self.writer.write_all(&value.to_be_bytes())
+    }
+
+    fn write_long(&mut self, value: i64, _tag: &str) -> io::Result<()> {
+        self.writer.write_all(&value.to_be_bytes())
+    }
+
+    fn write_float(&mut self, value: f32, _tag: &str) -> io::Result<()> {
+        // Java goes through floatToIntBits, which collapses every NaN onto
+        // the canonical one.
+        let value = if value.is_nan() { f32::NAN } else { value };
+        self.writer.write_all(&value.to_be_bytes())
+    }
+
+    fn write_double(&mut self, value: f64, _tag: &str) -> io::Result<()> {
+        let value = if value.is_nan() { f64::NAN } else { value };
+        self.writer.write_all(&value.to_be_bytes())
+    }
+
+    fn write_string(&mut self, value: Option<&str>, tag: &str) -> io::Result<()> {
+        self.write_buffer(value.map(str::as_bytes), tag)
+    }
+
+    fn write_buffer(&mut self, value: Option<&[u8]>, _tag: &str) -> io::Result<()> {
+        self.write_length(value.map(<[u8]>::len))?;
+        if let Some(value) = value {
+            self.writer.write_all(value)?;
+        }
+        Ok(())
+    }
+
+    fn start_record(&mut self, _tag: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn end_record(&mut self, _tag: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn start_vector(&mut self, len: Option<usize>, _tag: &str) -> io::Result<()> {
+        self.write_length(len)
+    }
+
+    fn end_vector(&mut self, _tag: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn start_map(&mut self, len: usize, _tag: &str) -> io::Result<()> {
+        self.write_length(Some(len))
+    }
+
+    fn end_map(&mut self, _tag: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads records in the layout of Java's `BinaryInputArchive`.
+pub struct BinaryInputArchive<R> {
+    reader: R,
+    max_buffer: usize,
+}
+
+impl<R: Read> BinaryInputArchive<R> {
+    pub fn new(reader: R) -> BinaryInputArchive<R> {
+        BinaryInputArchive {
+            reader,
+            max_buffer: DEFAULT_MAX_BUFFER,
+        }
+    }
+
+    /// Sets the largest string or buffer accepted before the input is
+    /// rejected as corrupt, like Java's `jute.maxbuffer`.
+    pub fn set_max_buffer(&mut self, max_buffer: usize) -> &mut BinaryInputArchive<R> {
+        self.max_buffer = max_buffer;
+        self
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0u8; N];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn read_length(&mut self) -> io::Result<Option<usize>> {
+        match i32::from_be_bytes(self.read_array()?) {
+            -1 => Ok(None),
+            len if len < 0 || len as usize > self.max_buffer => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unreasonable length = {}", len),
+            )),
+            len => Ok(Some(len as usize)),
+        }
+    }
+}
+
+impl<R: Read> InputArchive for BinaryInputArchive<R> {
+    fn read_byte(&mut self, _tag: &str) -> io::Result<i8> {
+        Ok(i8::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_bool(&mut self, _tag: &str) -> io::Result<bool> {
+        Ok(self.read_array::<1>()?[0] != 0)
+    }
+
+    fn read_int(&mut self, _tag: &str) -> io::Result<i32> {
+        Ok(i32::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_long(&mut self, _tag: &str) -> io::Result<i64> {
+        Ok(i64::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_float(&mut self, _tag: &str) -> io::Result<f32> {
+        Ok(f32::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_double(&mut self, _tag: &str) -> io::Result<f64> {
+        Ok(f64::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_string(&mut self, tag: &str) -> io::Result<Option<String>> {
+        match self.read_buffer(tag)? {
+            Some(bytes) => String::from_utf8(bytes)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            None => Ok(None),
+        }
+    }
+
+    fn read_buffer(&mut self, _tag: &str) -> io::Result<Option<Vec<u8>>> {
+        match self.read_length()? {
+            Some(len) => {
+                let mut bytes = vec![0u8; len];
+                self.reader.read_exact(&mut bytes)?;
+                Ok(Some(bytes))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn start_record(&mut self, _tag: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn end_record(&mut self, _tag: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn start_vector(&mut self, tag: &str) -> io::Result<Option<usize>> {
+        // Java only treats -1 as null; any other negative length is an
+        // index that is done right away.
+        match self.read_int(tag)? {
+            -1 => Ok(None),
+            len => Ok(Some(len.max(0) as usize)),
+        }
+    }
+
+    fn end_vector(&mut self, _tag: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn start_map(&mut self, tag: &str) -> io::Result<usize> {
+        Ok(self.read_int(tag)?.max(0) as usize)
+    }
+
+    fn end_map(&mut self, _tag: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serializes `record` with a `BinaryOutputArchive`.
+pub fn to_bytes<R: Record>(record: &R) -> io::Result<Vec<u8>> {
+    let mut archive = BinaryOutputArchive::new(vec![]);
+    record.serialize(&mut archive, "")?;
+    Ok(archive.into_inner())
+}
+
+/// Deserializes a record from the front of `bytes` with a `BinaryInputArchive`.
+pub fn from_bytes<R: Record>(bytes: &[u8]) -> io::Result<R> {
+    R::deserialize(&mut BinaryInputArchive::new(bytes), "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write<F>(f: F) -> Vec<u8>
+    where
+        F: FnOnce(&mut BinaryOutputArchive<&mut Vec<u8>>) -> io::Result<()>,
+    {
+        let mut bytes = vec![];
+        f(&mut BinaryOutputArchive::new(&mut bytes)).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_numbers_are_big_endian() {
+        assert_eq!(write(|a| a.write_int(0x01020304, "i")), vec![1, 2, 3, 4]);
+        assert_eq!(
+            write(|a| a.write_long(-2, "l")),
+            vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE]
+        );
+        assert_eq!(write(|a| a.write_float(1.0, "f")), vec![0x3F, 0x80, 0, 0]);
+        assert_eq!(
+            write(|a| a.write_double(-0.0, "d")),
+            vec![0x80, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(write(|a| a.write_bool(true, "b")), vec![1]);
+        assert_eq!(write(|a| a.write_byte(-2, "b")), vec![0xFE]);
+
+        let mut archive = BinaryInputArchive::new(&[0x80u8][..]);
+        assert_eq!(archive.read_byte("b").unwrap(), -128);
+    }
+
+    #[test]
+    fn test_nan_is_canonical() {
+        let nan = f32::from_bits(0x7FC0_0001);
+        assert_eq!(write(|a| a.write_float(nan, "f")), vec![0x7F, 0xC0, 0, 0]);
+    }
+
+    #[test]
+    fn test_null_and_empty_lengths() {
+        assert_eq!(
+            write(|a| a.write_string(None, "s")),
+            vec![0xFF, 0xFF, 0xFF, 0xFF]
+        );
+        assert_eq!(write(|a| a.write_buffer(Some(&[]), "b")), vec![0, 0, 0, 0]);
+        assert_eq!(
+            write(|a| a.start_vector(None, "v")),
+            vec![0xFF, 0xFF, 0xFF, 0xFF]
+        );
+
+        let bytes = [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF];
+        let mut archive = BinaryInputArchive::new(&bytes[..]);
+        assert_eq!(archive.read_string("s").unwrap(), None);
+        assert_eq!(archive.read_buffer("b").unwrap(), Some(vec![]));
+        assert_eq!(archive.start_vector("v").unwrap(), None);
+    }
+
+    #[test]
+    fn test_string_round_trip() {
+        let bytes = write(|a| a.write_string(Some("zöö"), "s"));
+        assert_eq!(bytes, vec![0, 0, 0, 5, b'z', 0xC3, 0xB6, 0xC3, 0xB6]);
+
+        let mut archive = BinaryInputArchive::new(&bytes[..]);
+        assert_eq!(archive.read_string("s").unwrap(), Some("zöö".to_string()));
+    }
+
+    #[test]
+    fn test_unreasonable_length() {
+        let bytes = [0x7F, 0xFF, 0xFF, 0xFF];
+        let mut archive = BinaryInputArchive::new(&bytes[..]);
+        assert!(archive.read_buffer("b").is_err());
+
+        let bytes = [0xFF, 0xFF, 0xFF, 0xFE];
+        let mut archive = BinaryInputArchive::new(&bytes[..]);
+        assert!(archive.read_string("s").is_err());
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let mut archive = BinaryInputArchive::new(&[0u8, 0, 0][..]);
+        assert!(archive.read_int("i").is_err());
+    }
+}
@@ -0,0 +1,25 @@
+use std::error;
+use std::fmt;
+
+/// Error returned by the `build` method of generated builders.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BuildError {
+    /// A field holding a nested record was not set. Unlike other fields,
+    /// records have no default to fall back to.
+    MissingField {
+        record: &'static str,
+        field: &'static str,
+    },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::MissingField { record, field } => {
+                write!(f, "field `{}` of `{}` is not set", field, record)
+            }
+        }
+    }
+}
+
+impl error::Error for BuildError {}
@@ -0,0 +1,29 @@
+/// Includes code that a build script generated with
+/// `jute_rust_codegen::Runner::out_dir`. Without arguments it includes the
+/// default `jute.rs`; otherwise it includes the file name given to
+/// `Runner::set_output`.
+///
+/// ```ignore
+/// // build.rs
+/// Runner::new()
+///     .add_file("src/zookeeper.jute".to_string())
+///     .out_dir()
+///     .run()
+///     .unwrap();
+///
+/// // src/lib.rs
+/// mod zookeeper {
+///     jute_rust_runtime::include_generated!();
+/// }
+/// ```
+///
+/// This expands to `include!(concat!(env!("OUT_DIR"), "/jute.rs"))`.
+#[macro_export]
+macro_rules! include_generated {
+    () => {
+        $crate::include_generated!("jute.rs");
+    };
+    ($file:literal) => {
+        include!(concat!(env!("OUT_DIR"), "/", $file));
+    };
+}
@@ -0,0 +1,300 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+/// Java's `hashCode` of a value, the counterpart of the `hashCode` method of
+/// records generated by Java's Jute compiler.
+///
+/// Values hash like their boxed Java types do inside a `List` or `Map`:
+/// a `bool` hashes to `1231` or `1237`, and a vector to `List.hashCode`.
+/// Generated records hash boolean and buffer fields the way Java's generated
+/// `hashCode` does instead.
+pub trait JavaHashCode {
+    fn java_hash_code(&self) -> i32;
+}
+
+impl JavaHashCode for bool {
+    fn java_hash_code(&self) -> i32 {
+        if *self {
+            1231
+        } else {
+            1237
+        }
+    }
+}
+
+impl JavaHashCode for i8 {
+    fn java_hash_code(&self) -> i32 {
+        i32::from(*self)
+    }
+}
+
+impl JavaHashCode for u8 {
+    fn java_hash_code(&self) -> i32 {
+        // Java's bytes are signed.
+        (*self as i8).java_hash_code()
+    }
+}
+
+impl JavaHashCode for i32 {
+    fn java_hash_code(&self) -> i32 {
+        *self
+    }
+}
+
+impl JavaHashCode for i64 {
+    fn java_hash_code(&self) -> i32 {
+        (*self ^ (*self as u64 >> 32) as i64) as i32
+    }
+}
+
+impl JavaHashCode for f32 {
+    fn java_hash_code(&self) -> i32 {
+        // `Float.floatToIntBits` maps every NaN to the canonical one.
+        let value = if self.is_nan() { f32::NAN } else { *self };
+        value.to_bits() as i32
+    }
+}
+
+impl JavaHashCode for f64 {
+    fn java_hash_code(&self) -> i32 {
+        let value = if self.is_nan() { f64::NAN } else { *self };
+        (value.to_bits() as i64).java_hash_code()
+    }
+}
+
+impl JavaHashCode for str {
+    fn java_hash_code(&self) -> i32 {
+        self.encode_utf16().fold(0i32, |hash, unit| {
+            hash.wrapping_mul(31).wrapping_add(i32::from(unit))
+        })
+    }
+}
+
+impl JavaHashCode for String {
+    fn java_hash_code(&self) -> i32 {
+        self.as_str().java_hash_code()
+    }
+}
+
+impl<T: JavaHashCode> JavaHashCode for Vec<T> {
+    fn java_hash_code(&self) -> i32 {
+        self.iter().fold(1i32, |hash, element| {
+            hash.wrapping_mul(31).wrapping_add(element.java_hash_code())
+        })
+    }
+}
+
+impl<K: JavaHashCode, V: JavaHashCode> JavaHashCode for BTreeMap<K, V> {
+    fn java_hash_code(&self) -> i32 {
+        map_hash_code(self)
+    }
+}
+
+impl<K: JavaHashCode, V: JavaHashCode, S> JavaHashCode for HashMap<K, V, S> {
+    fn java_hash_code(&self) -> i32 {
+        map_hash_code(self)
+    }
+}
+
+/// `Map.hashCode`: the sum of the hash codes of all entries.
+fn map_hash_code<'a, K, V, I>(entries: I) -> i32
+where
+    K: JavaHashCode + 'a,
+    V: JavaHashCode + 'a,
+    I: IntoIterator<Item = (&'a K, &'a V)>,
+{
+    entries.into_iter().fold(0i32, |hash, (key, value)| {
+        hash.wrapping_add(key.java_hash_code() ^ value.java_hash_code())
+    })
+}
+
+impl<T: JavaHashCode> JavaHashCode for Option<T> {
+    /// `Objects.hashCode`, which hashes null to `0`. Java's generated records
+    /// throw on null strings and vectors instead.
+    fn java_hash_code(&self) -> i32 {
+        self.as_ref().map_or(0, JavaHashCode::java_hash_code)
+    }
+}
+
+/// Java's generated hash code of a `buffer` field, the hash code of
+/// `Arrays.toString(buffer)`, which is `"null"` for a null buffer.
+pub fn buffer_hash_code(buffer: Option<&[u8]>) -> i32 {
+    match buffer {
+        Some(buffer) => {
+            let elements: Vec<String> = buffer
+                .iter()
+                .map(|&byte| (byte as i8).to_string())
+                .collect();
+            format!("[{}]", elements.join(", ")).java_hash_code()
+        }
+        None => "null".java_hash_code(),
+    }
+}
+
+/// Java's `compareTo`, the ordering of records generated by Java's Jute
+/// compiler.
+///
+/// Java only generates a working `compareTo` for records without vector or
+/// map fields, so only such records implement this trait.
+pub trait JavaComparable {
+    fn compare_to(&self, other: &Self) -> Ordering;
+}
+
+impl JavaComparable for bool {
+    fn compare_to(&self, other: &bool) -> Ordering {
+        self.cmp(other)
+    }
+}
+
+impl JavaComparable for i8 {
+    fn compare_to(&self, other: &i8) -> Ordering {
+        self.cmp(other)
+    }
+}
+
+impl JavaComparable for u8 {
+    fn compare_to(&self, other: &u8) -> Ordering {
+        (*self as i8).cmp(&(*other as i8))
+    }
+}
+
+impl JavaComparable for i32 {
+    fn compare_to(&self, other: &i32) -> Ordering {
+        self.cmp(other)
+    }
+}
+
+impl JavaComparable for i64 {
+    fn compare_to(&self, other: &i64) -> Ordering {
+        self.cmp(other)
+    }
+}
+
+impl JavaComparable for f32 {
+    fn compare_to(&self, other: &f32) -> Ordering {
+        compare_floats(self, other)
+    }
+}
+
+impl JavaComparable for f64 {
+    fn compare_to(&self, other: &f64) -> Ordering {
+        compare_floats(self, other)
+    }
+}
+
+/// Java's generated `(a == b) ? 0 : ((a < b) ? -1 : 1)`, which orders NaN
+/// after every number, itself included.
+fn compare_floats<F: PartialOrd>(a: &F, b: &F) -> Ordering {
+    if a == b {
+        Ordering::Equal
+    } else if a < b {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+impl JavaComparable for String {
+    /// `String.compareTo`, which compares UTF-16 code units rather than
+    /// code points.
+    fn compare_to(&self, other: &String) -> Ordering {
+        self.encode_utf16().cmp(other.encode_utf16())
+    }
+}
+
+impl JavaComparable for Vec<u8> {
+    /// `Utils.compareBytes`, which compares buffers as Java's signed bytes,
+    /// then by length.
+    fn compare_to(&self, other: &Vec<u8>) -> Ordering {
+        let signed = |buffer: &Vec<u8>| buffer.iter().map(|&byte| byte as i8).collect::<Vec<_>>();
+        signed(self).cmp(&signed(other))
+    }
+}
+
+impl<T: JavaComparable> JavaComparable for Option<T> {
+    /// Orders null first. Java's generated records throw on null strings and
+    /// buffers instead.
+    fn compare_to(&self, other: &Option<T>) -> Ordering {
+        match (self, other) {
+            (Some(a), Some(b)) => a.compare_to(b),
+            _ => self.is_some().cmp(&other.is_some()),
+        }
+    }
+}
+
+/// Java's `signature()`, the signature Java's Jute compiler gives a record,
+/// such as `LTxnHeader(lilli)`, which peers compare to detect schema
+/// mismatches.
+///
+/// Generated records write their `SIGNATURE` constant. Records deriving
+/// `JuteRecord` write the signatures of their nested records into theirs.
+pub trait JavaSignature {
+    /// Appends the signature to `signature`. `enclosing` names the records
+    /// whose signatures are being written, since the signature of a record
+    /// nested in itself stops at its name.
+    fn write_signature(signature: &mut String, enclosing: &mut Vec<&'static str>);
+
+    fn signature() -> String {
+        let mut signature = String::new();
+        Self::write_signature(&mut signature, &mut vec![]);
+        signature
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_codes() {
+        assert_eq!(true.java_hash_code(), 1231);
+        assert_eq!(0xFFu8.java_hash_code(), -1);
+        assert_eq!((-1i8).java_hash_code(), -1);
+        assert_eq!((-1i64).java_hash_code(), 0);
+        assert_eq!(0x1_0000_0002i64.java_hash_code(), 3);
+        assert_eq!(1.0f32.java_hash_code(), 1065353216);
+        assert_eq!(1.0f64.java_hash_code(), 1072693248);
+        assert_eq!(
+            f64::from_bits(0x7FF0_0000_0000_0001).java_hash_code(),
+            f64::NAN.java_hash_code()
+        );
+        assert_eq!("abc".java_hash_code(), 96354);
+        assert_eq!("".java_hash_code(), 0);
+        assert_eq!(vec![1, 2, 3].java_hash_code(), 30817);
+        assert_eq!(Vec::<i32>::new().java_hash_code(), 1);
+
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), 1);
+        map.insert("b".to_string(), 7);
+        assert_eq!(map.java_hash_code(), (97 ^ 1) + (98 ^ 7));
+    }
+
+    #[test]
+    fn test_buffer_hash_code() {
+        assert_eq!(buffer_hash_code(Some(&[])), "[]".java_hash_code());
+        assert_eq!(
+            buffer_hash_code(Some(&[1, 0xFF])),
+            "[1, -1]".java_hash_code()
+        );
+        assert_eq!(buffer_hash_code(None), "null".java_hash_code());
+        assert_eq!(None::<String>.java_hash_code(), 0);
+    }
+
+    #[test]
+    fn test_compare_to() {
+        assert_eq!(false.compare_to(&true), Ordering::Less);
+        assert_eq!(0x80u8.compare_to(&0x7F), Ordering::Less);
+        assert_eq!((-128i8).compare_to(&127), Ordering::Less);
+        assert_eq!(f64::NAN.compare_to(&f64::NAN), Ordering::Greater);
+        assert_eq!(1.0f64.compare_to(&f64::NAN), Ordering::Greater);
+        assert_eq!((-0.0f32).compare_to(&0.0), Ordering::Equal);
+        assert_eq!(
+            "\u{FFFD}".to_string().compare_to(&"\u{10000}".to_string()),
+            Ordering::Greater
+        );
+        assert_eq!(vec![0x80u8].compare_to(&vec![0x7F]), Ordering::Less);
+        assert_eq!(vec![1u8].compare_to(&vec![1, 0]), Ordering::Less);
+        assert_eq!(None.compare_to(&Some(vec![])), Ordering::Less);
+        assert_eq!(Some(1).compare_to(&Some(0)), Ordering::Greater);
+    }
+}
@@ -0,0 +1,12 @@
+mod archive;
+mod binary;
+mod builder;
+mod include;
+mod java;
+mod record;
+
+pub use archive::*;
+pub use binary::*;
+pub use builder::*;
+pub use java::*;
+pub use record::*;
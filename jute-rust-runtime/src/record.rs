@@ -0,0 +1,13 @@
+use std::io;
+
+use crate::{InputArchive, OutputArchive};
+
+/// A Jute record, the counterpart of Java's `org.apache.jute.Record`.
+///
+/// Generated structs implement this trait; hand-written types can implement
+/// it too and are then interchangeable with generated ones.
+pub trait Record: Sized {
+    fn serialize<A: OutputArchive>(&self, archive: &mut A, tag: &str) -> io::Result<()>;
+
+    fn deserialize<A: InputArchive>(archive: &mut A, tag: &str) -> io::Result<Self>;
+}